@@ -1,43 +1,192 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 
 use crate::{
-    dlmm::DLMMClient,
-    state::{MintAccount, PoolState, State, TokenMeta},
+    dlmm::{ClientRegistry, DLMMClient},
+    error::AppError,
+    pricing::ui_price_from_id,
+    state::{
+        bin_array_index_of, collect_bins, position_bin_amounts, AccountSource, BinResponse,
+        FixtureAccountSource, MintAccount, PairBinsResponse, PairMarket, PairMarketResponse,
+        PairRegistry, PairSummary, PendingReward, PoolState, Position, PositionBinAmounts,
+        PositionBinResponse, PositionEntry, PositionPnl, PositionResponse, RecordingAccountSource,
+        RpcAccountSource, State, TokenMeta, MAX_BIN_RANGE,
+    },
+    utils::to_ui_amount,
 };
-use anyhow::{Context, Ok, Result};
-use jupiter_amm_interface::{Amm, AmmContext, ClockRef, KeyedAccount};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{clock::Clock, pubkey::Pubkey, sysvar};
+use anyhow::{ensure, Context, Result};
+use futures_util::future::try_join_all;
+use jupiter_amm_interface::{Amm, AmmContext, ClockRef};
+use saros_sdk::state::bin_array::BinArray;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{clock::Clock, pubkey, pubkey::Pubkey, sysvar};
 use tokio::{sync::RwLock, time::Instant};
-use tracing::info;
+use tracing::{info, warn};
 
 use saros_dlmm::SarosDlmm;
+/// Saros DLMM program on mainnet
+pub const DLMM_PROGRAM_ID: Pubkey = pubkey!("1qbkdrr3z4ryLA7pZykqxvxWPoeifcVKo6ZG9CfkvVE");
+
+/// Number of bins listed on each side of the active bin when no range is given
+pub const DEFAULT_BIN_WINDOW: u32 = 50;
+
 #[derive(Clone)]
 pub struct TTLConfig {
     pub pool_ttl: Duration,
     pub token_ttl: Duration,
     pub bin_ttl: Duration,
+    /// How long an unused DLMMClient stays registered
+    pub client_ttl: Duration,
 }
 
 #[derive(Clone)]
 pub struct AppConfig {
     pub rpc_url: String,
+    /// Websocket endpoint used for account subscriptions
+    pub ws_url: String,
     pub cache_ttl: TTLConfig,
+    /// How often a pair is polled while its account subscription is down
+    pub quote_feed_interval: Duration,
+    /// Maximum number of DLMMClients kept alive at once
+    pub max_clients: usize,
+    /// Program scanned by the pair registry
+    pub dlmm_program_id: Pubkey,
+    /// How often the pair registry rescans the program
+    pub registry_refresh_interval: Duration,
+    /// How often the clock sysvar shared by every pair is read again
+    pub clock_refresh_interval: Duration,
+    /// Read accounts from this directory of JSON fixtures instead of the RPC node
+    pub fixtures_dir: Option<PathBuf>,
+    /// Save every account read into this directory, in the fixture format
+    pub record_dir: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// Builds the config from the defaults, overridden by any values set in `.env`
+    pub fn from_env() -> Self {
+        let mut config = AppConfig::default();
+
+        if let Ok(rpc_url) = dotenv::var("RPC_URL") {
+            info!("Using RPC URL from .env: {}", rpc_url);
+            config.ws_url = ws_url_from_rpc_url(&rpc_url);
+            config.rpc_url = rpc_url;
+        }
+
+        if let Ok(ws_url) = dotenv::var("WS_URL") {
+            info!("Using WS URL from .env: {}", ws_url);
+            config.ws_url = ws_url;
+        }
+
+        if let Ok(pool_ttl_secs) = dotenv::var("POOL_CACHE_TTL_SECS") {
+            if let Ok(pool_ttl) = pool_ttl_secs.parse::<u64>() {
+                info!("Using Pool Cache TTL from .env: {} seconds", pool_ttl);
+                config.cache_ttl.pool_ttl = Duration::from_secs(pool_ttl);
+            }
+        }
+
+        if let Ok(token_ttl_secs) = dotenv::var("TOKEN_CACHE_TTL_SECS") {
+            if let Ok(token_ttl) = token_ttl_secs.parse::<u64>() {
+                info!("Using Token Cache TTL from .env: {} seconds", token_ttl);
+                config.cache_ttl.token_ttl = Duration::from_secs(token_ttl);
+            }
+        }
+
+        if let Ok(bin_ttl_secs) = dotenv::var("BIN_CACHE_TTL_SECS") {
+            if let Ok(bin_ttl) = bin_ttl_secs.parse::<u64>() {
+                info!("Using Bin Cache TTL from .env: {} seconds", bin_ttl);
+                config.cache_ttl.bin_ttl = Duration::from_secs(bin_ttl);
+            }
+        }
+
+        if let Ok(client_ttl_secs) = dotenv::var("CLIENT_CACHE_TTL_SECS") {
+            if let Ok(client_ttl) = client_ttl_secs.parse::<u64>() {
+                info!("Using Client Cache TTL from .env: {} seconds", client_ttl);
+                config.cache_ttl.client_ttl = Duration::from_secs(client_ttl);
+            }
+        }
+
+        if let Ok(max_clients) = dotenv::var("MAX_DLMM_CLIENTS") {
+            if let Ok(max_clients) = max_clients.parse::<usize>() {
+                info!("Using Max DLMM Clients from .env: {}", max_clients);
+                config.max_clients = max_clients;
+            }
+        }
+
+        if let Ok(feed_interval_ms) = dotenv::var("QUOTE_FEED_INTERVAL_MS") {
+            if let Ok(feed_interval) = feed_interval_ms.parse::<u64>() {
+                info!("Using Quote Feed Interval from .env: {} ms", feed_interval);
+                config.quote_feed_interval = Duration::from_millis(feed_interval);
+            }
+        }
+
+        if let Ok(program_id) = dotenv::var("DLMM_PROGRAM_ID") {
+            if let Ok(program_id) = program_id.parse::<Pubkey>() {
+                info!("Using DLMM Program ID from .env: {}", program_id);
+                config.dlmm_program_id = program_id;
+            }
+        }
+
+        if let Ok(registry_secs) = dotenv::var("PAIR_REGISTRY_REFRESH_SECS") {
+            if let Ok(registry_refresh) = registry_secs.parse::<u64>() {
+                info!(
+                    "Using Pair Registry Refresh from .env: {} seconds",
+                    registry_refresh
+                );
+                config.registry_refresh_interval = Duration::from_secs(registry_refresh);
+            }
+        }
+
+        if let Ok(clock_interval_ms) = dotenv::var("CLOCK_REFRESH_INTERVAL_MS") {
+            if let Ok(clock_interval) = clock_interval_ms.parse::<u64>() {
+                info!(
+                    "Using Clock Refresh Interval from .env: {} ms",
+                    clock_interval
+                );
+                config.clock_refresh_interval = Duration::from_millis(clock_interval);
+            }
+        }
+
+        if let Ok(fixtures_dir) = dotenv::var("ACCOUNT_FIXTURES_DIR") {
+            info!("Using Account Fixtures Dir from .env: {}", fixtures_dir);
+            config.fixtures_dir = Some(PathBuf::from(fixtures_dir));
+        }
+
+        config
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         AppConfig {
             rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            ws_url: "wss://api.mainnet-beta.solana.com".to_string(),
             cache_ttl: TTLConfig {
                 pool_ttl: Duration::from_secs(15),     // 15 seconds
                 token_ttl: Duration::from_secs(43200), // 12 hours
                 bin_ttl: Duration::from_secs(15),      // 15 seconds
+                client_ttl: Duration::from_secs(600),  // 10 minutes
             },
+            max_clients: 100,
+            quote_feed_interval: Duration::from_millis(1000),
+            dlmm_program_id: DLMM_PROGRAM_ID,
+            registry_refresh_interval: Duration::from_secs(300), // 5 minutes
+            clock_refresh_interval: Duration::from_millis(1000),
+            fixtures_dir: None,
+            record_dir: None,
         }
     }
 }
 
+/// Most providers serve websockets on the same host as HTTP
+fn ws_url_from_rpc_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        rpc_url.to_string()
+    }
+}
+
 #[derive(Clone)]
 pub struct Cached<T> {
     pub value: Arc<T>,
@@ -61,70 +210,116 @@ impl<T> Cached<T> {
 #[derive(Clone)]
 pub struct AppContext {
     pub config: AppConfig,
+    /// Used for blockhashes and simulations, accounts are read through `accounts`
     pub rpc_client: Arc<RpcClient>,
-    pub pair_accounts: Arc<RwLock<HashMap<Pubkey, Cached<KeyedAccount>>>>,
+    pub accounts: Arc<dyn AccountSource>,
+    /// Source of the pair registry scans, which are never recorded: each one reads every pair
+    /// of the program
+    pub registry_accounts: Arc<dyn AccountSource>,
     pub pool_states: Arc<RwLock<HashMap<Pubkey, Cached<Option<PoolState>>>>>,
     pub mint_accounts: Arc<RwLock<HashMap<Pubkey, Cached<MintAccount>>>>,
     pub token_meta_cache: Arc<RwLock<HashMap<Pubkey, Cached<TokenMeta>>>>,
+    /// Bin arrays away from the active bin, keyed by pair and index
+    pub bin_arrays: Arc<RwLock<HashMap<(Pubkey, u32), Cached<BinArray>>>>,
+    pub dlmm_clients: ClientRegistry,
+    pub pair_registry: Arc<RwLock<PairRegistry>>,
+    /// Clock handed to every pair, fees decay and quotes are computed against it
+    pub clock_ref: ClockRef,
 }
 
 impl AppContext {
+    /// Reads accounts from `fixtures_dir` when set, from the RPC node otherwise.
+    /// With `record_dir` set, every account read is also saved there.
     pub fn new(config: AppConfig) -> Self {
         let rpc_client = Arc::new(RpcClient::new(config.rpc_url.clone()));
+        let unrecorded: Arc<dyn AccountSource> = match &config.fixtures_dir {
+            Some(dir) => {
+                info!("📂 Reading accounts from fixtures in {}", dir.display());
+                Arc::new(FixtureAccountSource::new(dir))
+            }
+            None => Arc::new(RpcAccountSource {
+                client: rpc_client.clone(),
+            }),
+        };
+        let accounts: Arc<dyn AccountSource> = match &config.record_dir {
+            Some(dir) => {
+                info!("⏺️ Recording accounts into {}", dir.display());
+                Arc::new(RecordingAccountSource::new(unrecorded.clone(), dir))
+            }
+            None => unrecorded.clone(),
+        };
+
+        AppContext {
+            registry_accounts: unrecorded,
+            ..Self::with_account_source(config, rpc_client, accounts)
+        }
+    }
+
+    pub fn with_account_source(
+        config: AppConfig,
+        rpc_client: Arc<RpcClient>,
+        accounts: Arc<dyn AccountSource>,
+    ) -> Self {
+        let dlmm_clients = ClientRegistry::new(config.cache_ttl.client_ttl, config.max_clients);
         AppContext {
             config,
             rpc_client,
-            pair_accounts: Arc::new(RwLock::new(HashMap::new())),
+            registry_accounts: accounts.clone(),
+            accounts,
             pool_states: Arc::new(RwLock::new(HashMap::new())),
             mint_accounts: Arc::new(RwLock::new(HashMap::new())),
             token_meta_cache: Arc::new(RwLock::new(HashMap::new())),
+            bin_arrays: Arc::new(RwLock::new(HashMap::new())),
+            dlmm_clients,
+            pair_registry: Arc::new(RwLock::new(PairRegistry::default())),
+            clock_ref: ClockRef::default(),
         }
     }
 
+    /// Returns the long-lived client of the pool, spawning and registering it on first use.
+    /// Registered clients are refreshed in place once stale, unless subscriptions keep them live.
     pub async fn get_or_spawn_client(&self, pool_key: Pubkey) -> Result<Arc<DLMMClient>> {
         let ttl = self.config.cache_ttl.clone();
-        let mut cached_pools = self.pair_accounts.write().await;
-        let mut cached_states = self.pool_states.write().await;
-        let mut cached_mints = self.mint_accounts.write().await;
-
-        let clock_data = self
-            .rpc_client
-            .get_account_with_commitment(&sysvar::clock::ID, self.rpc_client.commitment())?
-            .value
-            .context("Failed to get clock account")?;
 
-        let clock: Clock = bincode::deserialize(&clock_data.data)
-            .context("Failed to deserialize clock account data")?;
-
-        let amm_context = AmmContext {
-            clock_ref: ClockRef::try_from(clock)?,
-        };
-
-        if let Some(cached) = cached_pools.get(&pool_key) {
-            if !cached.is_expired(ttl.pool_ttl) {
-                info!("Using cached DLMMClient for pool: {:?}", pool_key);
-                let pair_account = cached.value.as_ref().clone();
-                let saros_dlmm = SarosDlmm::from_keyed_account(&pair_account, &amm_context)?;
-
-                return Ok(Arc::new(DLMMClient {
-                    saros_dlmm: Arc::new(RwLock::new(saros_dlmm)),
-                }));
+        if let Some((client, live)) = self.dlmm_clients.get(pool_key).await {
+            if !live && client.is_stale(ttl.pool_ttl).await {
+                info!("Refreshing cached DLMMClient for pool: {:?}", pool_key);
+                if let Err(e) = client.refresh(self).await {
+                    warn!(
+                        "⚠️ Failed to refresh DLMM client, serving its last state: {}",
+                        e
+                    );
+                }
             } else {
-                // ⚡ cleanup lazy
-                cached_pools.remove(&pool_key);
-                cached_states.remove(&pool_key);
+                info!("Using cached DLMMClient for pool: {:?}", pool_key);
             }
+            return Ok(client);
         }
 
         info!("Spawning new DLMMClient for pool: {:?}", pool_key);
-        let pair_account = State::generate_keyed_account(self.rpc_client.clone(), pool_key).await?;
-        cached_pools.insert(pool_key, Cached::new(pair_account.clone()));
+        let ((), pair_account) = tokio::try_join!(
+            self.refresh_clock(),
+            State::generate_keyed_account(self.accounts.clone(), pool_key)
+        )?;
+        ensure!(
+            pair_account.account.owner == self.config.dlmm_program_id,
+            AppError::PairNotFound(pool_key)
+        );
+
+        let amm_context = AmmContext {
+            clock_ref: self.clock_ref.clone(),
+        };
 
         let state =
-            State::generate_state_async(self.rpc_client.clone(), pair_account.clone()).await;
+            State::generate_state_async(self.accounts.clone(), pair_account.clone()).await?;
 
-        cached_states.insert(pool_key, Cached::new(state.pool_state));
+        // The caches are only locked once everything is fetched
+        self.pool_states
+            .write()
+            .await
+            .insert(pool_key, Cached::new(state.pool_state));
 
+        let mut cached_mints = self.mint_accounts.write().await;
         for mint_account in state.mint_accounts.iter() {
             if cached_mints.contains_key(&mint_account.key) {
                 if let Some(cached) = cached_mints.get(&mint_account.key) {
@@ -138,51 +333,462 @@ impl AppContext {
             }
             cached_mints.insert(mint_account.key, Cached::new(mint_account.clone()));
         }
+        drop(cached_mints);
+
+        let saros_dlmm = SarosDlmm::from_keyed_account(&pair_account.clone(), &amm_context)
+            .map_err(|e| AppError::DecodeFailure(format!("pair {}: {}", pool_key, e)))?;
+        let client = Arc::new(DLMMClient::new(saros_dlmm));
+        // The pair alone is loaded, the bin arrays follow on refresh
+        client.refresh(self).await?;
+
+        Ok(self.dlmm_clients.insert(pool_key, client).await)
+    }
+
+    /// Reads the clock sysvar into `clock_ref`, which every pair shares
+    pub async fn refresh_clock(&self) -> Result<()> {
+        let clock_account = self
+            .accounts
+            .get_account(&sysvar::clock::ID)
+            .await
+            .context("Failed to get clock account")?;
+        let clock: Clock = bincode::deserialize(&clock_account.data)
+            .map_err(|e| AppError::DecodeFailure(format!("clock account: {}", e)))?;
+
+        self.clock_ref.update(clock);
+        Ok(())
+    }
+
+    /// Keeps the clock moving for long-lived clients, unless accounts are static
+    pub fn spawn_clock_refresh(&self) {
+        if !self.accounts.is_live() {
+            return;
+        }
+
+        let ctx = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ctx.config.clock_refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = ctx.refresh_clock().await {
+                    warn!("⚠️ Failed to refresh clock: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Rescans the DLMM program and replaces the registry contents
+    pub async fn refresh_pair_registry(&self) -> Result<usize> {
+        let pairs = State::generate_program_pairs(
+            self.registry_accounts.clone(),
+            self.config.dlmm_program_id,
+        )
+        .await?;
+        let count = pairs.len();
+
+        let mut registry = self.pair_registry.write().await;
+        registry.pairs = pairs;
+        registry.last_refreshed = Some(Instant::now());
+
+        info!("📚 Pair registry refreshed with {} pairs", count);
+        Ok(count)
+    }
+
+    /// Keeps the pair registry fresh in the background
+    pub fn spawn_pair_registry_refresh(&self) {
+        let ctx = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ctx.config.registry_refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = ctx.refresh_pair_registry().await {
+                    warn!("⚠️ Failed to refresh pair registry: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Every registered pair trading the two mints, in either order, with its live market state
+    pub async fn find_pairs_by_mints(
+        &self,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+    ) -> Result<Vec<PairMarketResponse>> {
+        ensure!(
+            mint_a != mint_b,
+            AppError::InvalidInput("The two mints must be different".to_string())
+        );
+
+        let matches = {
+            let registry = self.pair_registry.read().await;
+            ensure!(
+                registry.last_refreshed.is_some(),
+                AppError::NotReady("Pair registry is still loading".to_string())
+            );
+            registry.find_by_mints(mint_a, mint_b)
+        };
+        if matches.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let (meta_a, meta_b, mint_account_a, mint_account_b) = tokio::try_join!(
+            self.get_token_meta(mint_a),
+            self.get_token_meta(mint_b),
+            self.get_mint_account(mint_a),
+            self.get_mint_account(mint_b)
+        )?;
+        let metas = HashMap::from([(mint_a, meta_a), (mint_b, meta_b)]);
+        let token_programs = HashMap::from([
+            (mint_a, mint_account_a.account.owner),
+            (mint_b, mint_account_b.account.owner),
+        ]);
+
+        let markets =
+            State::generate_pair_markets(self.accounts.clone(), matches, token_programs).await?;
+
+        let mut pairs: Vec<PairMarketResponse> = markets
+            .into_iter()
+            .map(|market| {
+                let token_x = &metas[&market.pair.token_mint_x];
+                let token_y = &metas[&market.pair.token_mint_y];
+
+                PairMarketResponse {
+                    pair_address: market.address.to_string(),
+                    token_mint_x: token_x.mint.to_string(),
+                    token_mint_y: token_y.mint.to_string(),
+                    symbol_x: token_x.symbol.clone(),
+                    symbol_y: token_y.symbol.clone(),
+                    bin_step: market.pair.bin_step,
+                    active_id: market.pair.active_id,
+                    price: ui_price_from_id(
+                        market.pair.bin_step,
+                        market.pair.active_id,
+                        token_x,
+                        token_y,
+                    ),
+                    reserve_x: to_ui_amount(market.reserve_x, token_x.decimals),
+                    reserve_y: to_ui_amount(market.reserve_y, token_y.decimals),
+                }
+            })
+            .collect();
+        pairs.sort_by_key(|pair| pair.bin_step);
 
-        let saros_dlmm = SarosDlmm::from_keyed_account(&pair_account.clone(), &amm_context)?;
-        let client = Arc::new(DLMMClient {
-            saros_dlmm: Arc::new(RwLock::new(saros_dlmm)),
+        Ok(pairs)
+    }
+
+    /// Values a single position, against `entry` when given
+    pub async fn get_position(
+        &self,
+        address: Pubkey,
+        entry: Option<PositionEntry>,
+    ) -> Result<PositionResponse> {
+        let position =
+            State::generate_position(self.accounts.clone(), address, self.config.dlmm_program_id)
+                .await?;
+
+        self.position_response(&position, entry.as_ref()).await
+    }
+
+    /// Every DLMM position held by `owner`, valued with the tokens it holds in each bin
+    pub async fn get_positions(&self, owner: Pubkey) -> Result<Vec<PositionResponse>> {
+        let positions = State::generate_owner_positions(
+            self.accounts.clone(),
+            owner,
+            self.config.dlmm_program_id,
+        )
+        .await?;
+        info!("Found {} positions for owner: {:?}", positions.len(), owner);
+
+        let mut responses = try_join_all(
+            positions
+                .iter()
+                .map(|position| self.position_response(position, None)),
+        )
+        .await?;
+        responses.sort_by(|a, b| {
+            (&a.pair_address, a.lower_bin_id).cmp(&(&b.pair_address, b.lower_bin_id))
         });
 
-        Ok(client)
+        Ok(responses)
     }
 
-    pub async fn fetch_pair_token_info(&self, dlmm_client: &SarosDlmm) -> Result<[TokenMeta; 2]> {
-        let mut mint_a_state = TokenMeta::default();
-        let mut mint_b_state = TokenMeta::default();
+    async fn position_response(
+        &self,
+        position: &Position,
+        entry: Option<&PositionEntry>,
+    ) -> Result<PositionResponse> {
+        let dlmm_client = self.get_or_spawn_client(position.pair).await?;
+        let pair = dlmm_client.pair_summary().await;
+        let hook = dlmm_client.saros_dlmm.read().await.pair.hook;
 
-        let mut token_cache = self.token_meta_cache.write().await;
-        let ttl = self.config.cache_ttl.token_ttl;
+        let [token_x, token_y] = self
+            .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+            .await?;
+        let bin_arrays = self
+            .get_bin_arrays(
+                position.pair,
+                self.config.dlmm_program_id,
+                position.bin_array_indexes(),
+            )
+            .await?;
 
-        let mint_x = dlmm_client.pair.token_mint_x;
-        let mint_y = dlmm_client.pair.token_mint_y;
+        let bins: Vec<PositionBinAmounts> = position_bin_amounts(position, &bin_arrays)
+            .into_iter()
+            .filter(|bin| bin.liquidity_share > 0)
+            .collect();
+        let amount_x = to_ui_amount(bins.iter().map(|bin| bin.amount_x).sum(), token_x.decimals);
+        let amount_y = to_ui_amount(bins.iter().map(|bin| bin.amount_y).sum(), token_y.decimals);
 
-        if let Some(cached_mint_x) = token_cache.get(&mint_x) {
-            if !cached_mint_x.is_expired(ttl) {
-                info!("Using cached token meta for mint_x: {:?}", mint_x);
-                mint_a_state = cached_mint_x.value.as_ref().clone();
-            } else {
-                // ⚡ cleanup lazy
-                token_cache.remove(&mint_x);
+        let price = ui_price_from_id(pair.bin_step, pair.active_id, &token_x, &token_y);
+        let value = amount_x * price + amount_y;
+        let pending_reward = match hook {
+            Some(hook) => self.pending_reward(hook, position).await,
+            None => None,
+        };
+
+        Ok(PositionResponse {
+            position_address: position.address.to_string(),
+            position_mint: position.position_mint.to_string(),
+            pair_address: position.pair.to_string(),
+            token_mint_x: token_x.mint.to_string(),
+            token_mint_y: token_y.mint.to_string(),
+            symbol_x: token_x.symbol.clone(),
+            symbol_y: token_y.symbol.clone(),
+            lower_bin_id: position.lower_bin_id,
+            upper_bin_id: position.upper_bin_id,
+            active_id: pair.active_id,
+            in_range: (position.lower_bin_id..=position.upper_bin_id).contains(&pair.active_id),
+            price,
+            amount_x,
+            amount_y,
+            pending_reward,
+            value,
+            pnl: entry.map(|entry| PositionPnl::new(entry, price, value)),
+            bins: bins
+                .into_iter()
+                .map(|bin| PositionBinResponse {
+                    bin_id: bin.bin_id,
+                    price: ui_price_from_id(pair.bin_step, bin.bin_id, &token_x, &token_y),
+                    liquidity_share: bin.liquidity_share.to_string(),
+                    amount_x: to_ui_amount(bin.amount_x, token_x.decimals),
+                    amount_y: to_ui_amount(bin.amount_y, token_y.decimals),
+                })
+                .collect(),
+        })
+    }
+
+    /// Rewards of a position on a pair with a rewarder hook. A hook that cannot be read is
+    /// logged and left out rather than failing the whole valuation.
+    async fn pending_reward(&self, hook: Pubkey, position: &Position) -> Option<PendingReward> {
+        let reward = async {
+            let hook =
+                State::generate_rewarder_hook(self.accounts.clone(), hook, position.pair).await?;
+            let (raw_amount, token) = tokio::try_join!(
+                State::generate_pending_rewards(self.accounts.clone(), &hook, position),
+                self.get_token_meta(hook.reward_token_mint)
+            )?;
+
+            Ok::<_, anyhow::Error>(PendingReward {
+                reward_mint: token.mint.to_string(),
+                symbol: token.symbol.clone(),
+                raw_amount,
+                amount: to_ui_amount(raw_amount, token.decimals),
+            })
+        };
+
+        match reward.await {
+            Ok(reward) => Some(reward),
+            Err(e) => {
+                warn!(
+                    "⚠️ Failed to read the rewards of position {:?}: {}",
+                    position.address, e
+                );
+                None
             }
-        } else {
-            mint_a_state = State::generate_token_state(self.rpc_client.clone(), mint_x).await?;
-            token_cache.insert(mint_x, Cached::new(mint_a_state.clone()));
         }
+    }
 
-        if let Some(cached_mint_y) = token_cache.get(&mint_y) {
-            if !cached_mint_y.is_expired(ttl) {
-                info!("Using cached token meta for mint_y: {:?}", mint_y);
-                mint_b_state = cached_mint_y.value.as_ref().clone();
-            } else {
-                // ⚡ cleanup lazy
-                token_cache.remove(&mint_y);
+    pub async fn get_mint_account(&self, mint_key: Pubkey) -> Result<MintAccount> {
+        if let Some(cached) = self.mint_accounts.read().await.get(&mint_key) {
+            if !cached.is_expired(self.config.cache_ttl.token_ttl) {
+                return Ok(cached.value.as_ref().clone());
+            }
+        }
+
+        let mint_account = State::generate_mint_account(self.accounts.clone(), mint_key).await?;
+        self.mint_accounts
+            .write()
+            .await
+            .insert(mint_key, Cached::new(mint_account.clone()));
+
+        Ok(mint_account)
+    }
+
+    pub async fn get_token_meta(&self, mint_key: Pubkey) -> Result<TokenMeta> {
+        let ttl = self.config.cache_ttl.token_ttl;
+
+        if let Some(cached) = self.token_meta_cache.read().await.get(&mint_key) {
+            if !cached.is_expired(ttl) {
+                info!("Using cached token meta for mint: {:?}", mint_key);
+                return Ok(cached.value.as_ref().clone());
             }
-        } else {
-            mint_b_state = State::generate_token_state(self.rpc_client.clone(), mint_y).await?;
-            token_cache.insert(mint_y, Cached::new(mint_b_state.clone()));
         }
 
+        let token_meta = State::generate_token_state(self.accounts.clone(), mint_key).await?;
+        self.token_meta_cache
+            .write()
+            .await
+            .insert(mint_key, Cached::new(token_meta.clone()));
+
+        Ok(token_meta)
+    }
+
+    pub async fn fetch_pair_token_info(
+        &self,
+        token_mint_x: Pubkey,
+        token_mint_y: Pubkey,
+    ) -> Result<[TokenMeta; 2]> {
+        let (mint_a_state, mint_b_state) = tokio::try_join!(
+            self.get_token_meta(token_mint_x),
+            self.get_token_meta(token_mint_y)
+        )?;
+
         Ok([mint_a_state, mint_b_state])
     }
+
+    /// Reads the pair and both of its vaults in one call, so they are consistent with each other
+    pub async fn get_pair_market(&self, pair: &PairSummary) -> Result<PairMarket> {
+        let (mint_account_x, mint_account_y) = tokio::try_join!(
+            self.get_mint_account(pair.token_mint_x),
+            self.get_mint_account(pair.token_mint_y)
+        )?;
+        let token_programs = HashMap::from([
+            (pair.token_mint_x, mint_account_x.account.owner),
+            (pair.token_mint_y, mint_account_y.account.owner),
+        ]);
+
+        State::generate_pair_markets(self.accounts.clone(), vec![pair.clone()], token_programs)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::PairNotFound(pair.address).into())
+    }
+
+    /// Loads the bin arrays with the given indexes, reusing the ones cached in `PoolState` or
+    /// fetched by an earlier call. Arrays that are not initialized on chain are left out.
+    pub async fn get_bin_arrays(
+        &self,
+        pair_key: Pubkey,
+        program_id: Pubkey,
+        indexes: Vec<u32>,
+    ) -> Result<HashMap<u32, BinArray>> {
+        let mut bin_arrays = HashMap::new();
+
+        if let Some(cached) = self.pool_states.read().await.get(&pair_key) {
+            if let Some(pool_state) = cached.value.as_ref() {
+                if !cached.is_expired(self.config.cache_ttl.bin_ttl) {
+                    for bin_array in [
+                        &pool_state.active_bin_array_lower,
+                        &pool_state.active_bin_array_upper,
+                    ] {
+                        if indexes.contains(&bin_array.index) {
+                            bin_arrays.insert(bin_array.index, bin_array.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        {
+            let cached_arrays = self.bin_arrays.read().await;
+            for index in indexes.iter() {
+                if bin_arrays.contains_key(index) {
+                    continue;
+                }
+                if let Some(cached) = cached_arrays.get(&(pair_key, *index)) {
+                    if !cached.is_expired(self.config.cache_ttl.bin_ttl) {
+                        bin_arrays.insert(*index, cached.value.as_ref().clone());
+                    }
+                }
+            }
+        }
+
+        let missing: Vec<u32> = indexes
+            .into_iter()
+            .filter(|index| !bin_arrays.contains_key(index))
+            .collect();
+        if !missing.is_empty() {
+            info!(
+                "Fetching {} extra bin arrays for pair: {:?}",
+                missing.len(),
+                pair_key
+            );
+            let fetched =
+                State::generate_bin_arrays(self.accounts.clone(), pair_key, program_id, missing)
+                    .await?;
+
+            let mut cached_arrays = self.bin_arrays.write().await;
+            cached_arrays.retain(|_, cached| !cached.is_expired(self.config.cache_ttl.bin_ttl));
+            for (index, bin_array) in fetched.iter() {
+                cached_arrays.insert((pair_key, *index), Cached::new(bin_array.clone()));
+            }
+            drop(cached_arrays);
+
+            bin_arrays.extend(fetched);
+        }
+
+        Ok(bin_arrays)
+    }
+
+    /// Lists the liquidity of every bin in `from..=to`, defaulting to a window around the active bin.
+    /// The bin arrays cached in `PoolState` are reused, any other array in the range is fetched.
+    pub async fn fetch_bin_range(
+        &self,
+        pair_key: Pubkey,
+        from: Option<u32>,
+        to: Option<u32>,
+    ) -> Result<PairBinsResponse> {
+        let dlmm_client = self.get_or_spawn_client(pair_key).await?;
+        let pair = dlmm_client.pair_summary().await;
+
+        let from = from.unwrap_or(pair.active_id.saturating_sub(DEFAULT_BIN_WINDOW));
+        let to = to.unwrap_or(pair.active_id.saturating_add(DEFAULT_BIN_WINDOW));
+        ensure!(
+            from <= to,
+            AppError::InvalidInput("`from` must not be greater than `to`".to_string())
+        );
+        ensure!(
+            to - from < MAX_BIN_RANGE,
+            AppError::InvalidInput(format!("Bin range is limited to {} bins", MAX_BIN_RANGE))
+        );
+
+        let [token_x, token_y] = self
+            .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+            .await?;
+
+        let indexes: Vec<u32> = (bin_array_index_of(from)..=bin_array_index_of(to)).collect();
+        let bin_arrays = self
+            .get_bin_arrays(pair_key, self.config.dlmm_program_id, indexes)
+            .await?;
+
+        let bins = collect_bins(&bin_arrays, from, to)
+            .into_iter()
+            .map(|bin| BinResponse {
+                id: bin.id,
+                price: ui_price_from_id(pair.bin_step, bin.id, &token_x, &token_y),
+                reserve_x: to_ui_amount(bin.reserve_x, token_x.decimals),
+                reserve_y: to_ui_amount(bin.reserve_y, token_y.decimals),
+            })
+            .collect();
+
+        Ok(PairBinsResponse {
+            pair_address: pair_key.to_string(),
+            token_mint_x: token_x.mint.to_string(),
+            token_mint_y: token_y.mint.to_string(),
+            symbol_x: token_x.symbol,
+            symbol_y: token_y.symbol,
+            bin_step: pair.bin_step,
+            active_id: pair.active_id,
+            bins,
+        })
+    }
 }
@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use solana_sdk::pubkey::Pubkey;
+use std::path::PathBuf;
+
+use crate::state::{LiquidityShape, QuoteSwapMode, DEFAULT_SLIPPAGE_BPS};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Saros DLMM Interface CLI")]
@@ -14,5 +18,198 @@ pub enum Commands {
         /// Enable web interface
         #[arg(long)]
         web: bool,
+        /// Save every account read, with its slot, into this directory
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+        /// Serve every account from a directory saved with --record instead of the RPC node
+        #[arg(long, value_name = "DIR")]
+        replay: Option<PathBuf>,
+    },
+    /// Quote a swap on a pair without starting the web server
+    Quote(QuoteArgs),
+    /// Inspect pairs
+    Pair {
+        #[command(subcommand)]
+        command: PairCommands,
+    },
+    /// Inspect liquidity positions
+    Position {
+        #[command(subcommand)]
+        command: PositionCommands,
     },
-}
\ No newline at end of file
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PositionCommands {
+    /// List the positions held by a wallet, with the tokens in each bin
+    List {
+        /// Wallet holding the position NFTs
+        owner: Pubkey,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Value a position: amounts, fees, value in token Y and PnL against an entry snapshot
+    Show {
+        /// Position address
+        address: Pubkey,
+        /// Amount of X deposited, in UI units
+        #[arg(long, requires_all = ["entry_amount_y", "entry_price"])]
+        entry_amount_x: Option<f64>,
+        /// Amount of Y deposited, in UI units
+        #[arg(long, requires_all = ["entry_amount_x", "entry_price"])]
+        entry_amount_y: Option<f64>,
+        /// Price of one X in Y when the position was opened
+        #[arg(long, requires_all = ["entry_amount_x", "entry_amount_y"])]
+        entry_price: Option<f64>,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Build the unsigned transaction opening a position and depositing into it
+    Create {
+        /// Pair address
+        pair: Pubkey,
+        /// Wallet paying for and owning the position
+        user: Pubkey,
+        /// Lowest bin of the position
+        #[arg(long, requires = "upper_bin_id", conflicts_with_all = ["min_price", "max_price"])]
+        lower_bin_id: Option<u32>,
+        /// Highest bin of the position
+        #[arg(long, requires = "lower_bin_id")]
+        upper_bin_id: Option<u32>,
+        /// Lowest price of one X in Y covered by the position
+        #[arg(long, requires = "max_price")]
+        min_price: Option<f64>,
+        /// Highest price of one X in Y covered by the position
+        #[arg(long, requires = "min_price")]
+        max_price: Option<f64>,
+        /// Raw amount of X to deposit
+        #[arg(long, default_value_t = 0)]
+        amount_x: u64,
+        /// Raw amount of Y to deposit
+        #[arg(long, default_value_t = 0)]
+        amount_y: u64,
+        /// How the deposit is spread across the bins
+        #[arg(long, value_enum, default_value_t = LiquidityShape::Spot)]
+        shape: LiquidityShape,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Build the unsigned transaction depositing more into a position, over all of its bins
+    Add {
+        /// Position address
+        address: Pubkey,
+        /// Wallet holding the position NFT
+        user: Pubkey,
+        /// Raw amount of X to deposit
+        #[arg(long, default_value_t = 0)]
+        amount_x: u64,
+        /// Raw amount of Y to deposit
+        #[arg(long, default_value_t = 0)]
+        amount_y: u64,
+        /// How the deposit is spread across the bins
+        #[arg(long, value_enum, default_value_t = LiquidityShape::Spot)]
+        shape: LiquidityShape,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Build the unsigned transaction withdrawing part of a position's liquidity
+    Remove {
+        /// Position address
+        address: Pubkey,
+        /// Wallet holding the position NFT
+        user: Pubkey,
+        /// Part of the liquidity withdrawn from each bin, 10000 empties them
+        #[arg(long, default_value_t = 10_000)]
+        bps: u16,
+        /// Only withdraw from these bins, comma separated
+        #[arg(long, value_delimiter = ',')]
+        bins: Option<Vec<u32>>,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Build the unsigned transactions claiming the rewards of every position of a wallet
+    Claim {
+        /// Wallet holding the position NFTs
+        user: Pubkey,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PairCommands {
+    /// Dump the full on-chain state of a pair
+    Inspect {
+        /// Pair address
+        address: Pubkey,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find every pair trading two mints, in either order
+    Find {
+        /// First mint
+        mint_a: Pubkey,
+        /// Second mint
+        mint_b: Pubkey,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the liquidity of each bin in a range, around the active bin by default
+    Bins {
+        /// Pair address
+        address: Pubkey,
+        /// First bin id of the range
+        #[arg(long)]
+        from: Option<u32>,
+        /// Last bin id of the range
+        #[arg(long)]
+        to: Option<u32>,
+        /// Print the result as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save the accounts a swap on the pair uses as fixtures, for `--replay` and the program tests
+    Record {
+        /// Pair address
+        address: Pubkey,
+        /// Directory the fixtures are written to
+        #[arg(long, default_value = "tests/fixtures/pair")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct QuoteArgs {
+    /// Pair address
+    #[arg(long)]
+    pub pair: Pubkey,
+    /// Mint of the token being sold
+    #[arg(long = "in")]
+    pub source_mint: Pubkey,
+    /// Mint of the token being bought
+    #[arg(long = "out")]
+    pub destination_mint: Pubkey,
+    /// Amount in UI units (e.g. `1.5`), sold for `exact-in` and bought for `exact-out`
+    #[arg(long)]
+    pub amount: String,
+    /// Which side of the swap `--amount` fixes
+    #[arg(long, value_enum, default_value_t = QuoteSwapMode::ExactIn)]
+    pub mode: QuoteSwapMode,
+    /// Slippage tolerance used for the minimum received / maximum spent
+    #[arg(long, default_value_t = DEFAULT_SLIPPAGE_BPS)]
+    pub slippage_bps: u16,
+    /// Also show each bin the swap goes through
+    #[arg(long)]
+    pub verbose: bool,
+    /// Print the result as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
@@ -0,0 +1,3 @@
+pub mod pair;
+pub mod position;
+pub mod quote;
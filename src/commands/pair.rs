@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use jupiter_amm_interface::SwapMode;
+use saros_sdk::{state::pair::Pair, utils::helper::get_pair_bin_array};
+use serde::Serialize;
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+use crate::{
+    app::AppContext,
+    dlmm::{
+        build_swap_instruction,
+        fees::{base_fee, fee_to_bps, total_fee, variable_fee},
+        SwapInstructionParams,
+    },
+    pricing::{price_from_id, to_ui_price},
+    state::{bin_array_index_of, State, TokenMeta},
+};
+
+#[derive(Serialize)]
+struct TokenView {
+    mint: String,
+    symbol: String,
+    decimals: u8,
+}
+
+impl From<&TokenMeta> for TokenView {
+    fn from(meta: &TokenMeta) -> Self {
+        TokenView {
+            mint: meta.mint.to_string(),
+            symbol: meta.symbol.clone(),
+            decimals: meta.decimals,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeeView {
+    base_factor: u16,
+    filter_period: u16,
+    decay_period: u16,
+    reduction_factor: u16,
+    variable_fee_control: u32,
+    max_volatility_accumulator: u32,
+    protocol_share: u16,
+    time_last_updated: u64,
+    volatility_accumulator: u32,
+    volatility_reference: u32,
+    id_reference: u32,
+    base_fee_bps: f64,
+    variable_fee_bps: f64,
+    total_fee_bps: f64,
+    protocol_fees_x: u64,
+    protocol_fees_y: u64,
+}
+
+#[derive(Serialize)]
+struct PairInspection {
+    pair_address: String,
+    program_id: String,
+    token_x: TokenView,
+    token_y: TokenView,
+    bin_step: u8,
+    active_id: u32,
+    /// Raw price of X in Y at the active bin
+    active_price: f64,
+    /// Price of one X in Y, adjusted for decimals
+    active_price_ui: f64,
+    fees: FeeView,
+    bin_array_index: u32,
+    bin_array_lower: String,
+    bin_array_upper: String,
+}
+
+pub async fn inspect(ctx: &AppContext, address: Pubkey, json: bool) -> Result<()> {
+    let pair_account = State::generate_keyed_account(ctx.accounts.clone(), address).await?;
+    let pair = Pair::unpack(&pair_account.account.data).context("Failed to unpack pair account")?;
+
+    let (token_x, token_y) = tokio::try_join!(
+        State::generate_token_state(ctx.accounts.clone(), pair.token_mint_x),
+        State::generate_token_state(ctx.accounts.clone(), pair.token_mint_y)
+    )?;
+
+    let bin_array_index = pair.bin_array_index();
+    let (bin_array_lower, bin_array_upper) =
+        get_pair_bin_array(bin_array_index, &address, &pair_account.account.owner);
+
+    let active_price = price_from_id(pair.bin_step, pair.active_id);
+    let active_price_ui = to_ui_price(active_price, token_x.decimals, token_y.decimals);
+
+    let static_fee = &pair.static_fee_parameters;
+    let dynamic_fee = &pair.dynamic_fee_parameters;
+
+    let inspection = PairInspection {
+        pair_address: address.to_string(),
+        program_id: pair_account.account.owner.to_string(),
+        token_x: TokenView::from(&token_x),
+        token_y: TokenView::from(&token_y),
+        bin_step: pair.bin_step,
+        active_id: pair.active_id,
+        active_price,
+        active_price_ui,
+        fees: FeeView {
+            base_factor: static_fee.base_factor,
+            filter_period: static_fee.filter_period,
+            decay_period: static_fee.decay_period,
+            reduction_factor: static_fee.reduction_factor,
+            variable_fee_control: static_fee.variable_fee_control,
+            max_volatility_accumulator: static_fee.max_volatility_accumulator,
+            protocol_share: static_fee.protocol_share,
+            time_last_updated: dynamic_fee.time_last_updated,
+            volatility_accumulator: dynamic_fee.volatility_accumulator,
+            volatility_reference: dynamic_fee.volatility_reference,
+            id_reference: dynamic_fee.id_reference,
+            base_fee_bps: fee_to_bps(base_fee(&pair)),
+            variable_fee_bps: fee_to_bps(variable_fee(&pair, dynamic_fee.volatility_accumulator)),
+            total_fee_bps: fee_to_bps(total_fee(&pair)),
+            protocol_fees_x: pair.protocol_fees_x,
+            protocol_fees_y: pair.protocol_fees_y,
+        },
+        bin_array_index,
+        bin_array_lower: bin_array_lower.to_string(),
+        bin_array_upper: bin_array_upper.to_string(),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&inspection)?);
+        return Ok(());
+    }
+
+    print_inspection(&inspection);
+    Ok(())
+}
+
+fn print_inspection(inspection: &PairInspection) {
+    let fees = &inspection.fees;
+    let rows = [
+        ("Pair", inspection.pair_address.clone()),
+        ("Program", inspection.program_id.clone()),
+        (
+            "Token X",
+            format!(
+                "{} {} (decimals {})",
+                inspection.token_x.symbol, inspection.token_x.mint, inspection.token_x.decimals
+            ),
+        ),
+        (
+            "Token Y",
+            format!(
+                "{} {} (decimals {})",
+                inspection.token_y.symbol, inspection.token_y.mint, inspection.token_y.decimals
+            ),
+        ),
+        ("Bin step", inspection.bin_step.to_string()),
+        ("Active id", inspection.active_id.to_string()),
+        (
+            "Price",
+            format!(
+                "{} {} per {} (raw {})",
+                inspection.active_price_ui,
+                inspection.token_y.symbol,
+                inspection.token_x.symbol,
+                inspection.active_price
+            ),
+        ),
+        ("Base factor", fees.base_factor.to_string()),
+        ("Filter period", fees.filter_period.to_string()),
+        ("Decay period", fees.decay_period.to_string()),
+        ("Reduction factor", fees.reduction_factor.to_string()),
+        ("Var. fee control", fees.variable_fee_control.to_string()),
+        ("Max vol. acc.", fees.max_volatility_accumulator.to_string()),
+        ("Protocol share", fees.protocol_share.to_string()),
+        ("Last updated", fees.time_last_updated.to_string()),
+        ("Vol. accumulator", fees.volatility_accumulator.to_string()),
+        ("Vol. reference", fees.volatility_reference.to_string()),
+        ("Id reference", fees.id_reference.to_string()),
+        ("Base fee (bps)", fees.base_fee_bps.to_string()),
+        ("Var. fee (bps)", fees.variable_fee_bps.to_string()),
+        ("Total fee (bps)", fees.total_fee_bps.to_string()),
+        ("Protocol fees X", fees.protocol_fees_x.to_string()),
+        ("Protocol fees Y", fees.protocol_fees_y.to_string()),
+        ("Bin array index", inspection.bin_array_index.to_string()),
+        ("Bin array lower", inspection.bin_array_lower.clone()),
+        ("Bin array upper", inspection.bin_array_upper.clone()),
+    ];
+
+    for (label, value) in rows {
+        println!("{:<18} {}", label, value);
+    }
+}
+
+pub async fn find(ctx: &AppContext, mint_a: Pubkey, mint_b: Pubkey, json: bool) -> Result<()> {
+    ctx.refresh_pair_registry().await?;
+    let pairs = ctx.find_pairs_by_mints(mint_a, mint_b).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&pairs)?);
+        return Ok(());
+    }
+
+    if pairs.is_empty() {
+        println!("No pair found for {} and {}", mint_a, mint_b);
+        return Ok(());
+    }
+
+    println!(
+        "{:<44} {:>8} {:>10} {:>20} {:>20} {:>20}",
+        "Pair", "Bin step", "Active id", "Price", "Reserve X", "Reserve Y"
+    );
+    for pair in &pairs {
+        println!(
+            "{:<44} {:>8} {:>10} {:>20} {:>20} {:>20}",
+            pair.pair_address,
+            pair.bin_step,
+            pair.active_id,
+            format!("{} {}/{}", pair.price, pair.symbol_y, pair.symbol_x),
+            format!("{} {}", pair.reserve_x, pair.symbol_x),
+            format!("{} {}", pair.reserve_y, pair.symbol_y)
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn bins(
+    ctx: &AppContext,
+    address: Pubkey,
+    from: Option<u32>,
+    to: Option<u32>,
+    json: bool,
+) -> Result<()> {
+    let pair_bins = ctx.fetch_bin_range(address, from, to).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&pair_bins)?);
+        return Ok(());
+    }
+
+    println!(
+        "Pair {} ({}-{}), bin step {}, active id {}",
+        pair_bins.pair_address,
+        pair_bins.symbol_x,
+        pair_bins.symbol_y,
+        pair_bins.bin_step,
+        pair_bins.active_id
+    );
+    println!(
+        "{:<10} {:>20} {:>20} {:>20}",
+        "Bin", "Price", pair_bins.symbol_x, pair_bins.symbol_y
+    );
+    for bin in &pair_bins.bins {
+        let marker = if bin.id == pair_bins.active_id {
+            "*"
+        } else {
+            ""
+        };
+        println!(
+            "{:<10} {:>20} {:>20} {:>20}",
+            format!("{}{}", bin.id, marker),
+            bin.price,
+            bin.reserve_x,
+            bin.reserve_y
+        );
+    }
+
+    Ok(())
+}
+
+/// Reads every account a swap on the pair touches, in both directions, and the accounts of its
+/// rewarder hook through a context recording them as fixtures
+pub async fn record(ctx: &AppContext, address: Pubkey) -> Result<()> {
+    let dlmm_client = ctx.get_or_spawn_client(address).await?;
+    let pair = dlmm_client.pair_summary().await;
+
+    ctx.fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+    ctx.get_pair_market(&pair).await?;
+
+    let mut keys = vec![];
+    for (source_mint, destination_mint) in [
+        (pair.token_mint_x, pair.token_mint_y),
+        (pair.token_mint_y, pair.token_mint_x),
+    ] {
+        let params = SwapInstructionParams {
+            user: Pubkey::default(),
+            source_mint,
+            destination_mint,
+            source_token_program: ctx.get_mint_account(source_mint).await?.account.owner,
+            destination_token_program: ctx.get_mint_account(destination_mint).await?.account.owner,
+            amount: 1,
+            other_amount_threshold: 0,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let instruction = build_swap_instruction(&*dlmm_client.saros_dlmm.read().await, &params)?;
+        keys.extend(instruction.accounts.iter().map(|meta| meta.pubkey));
+    }
+    // The rewarder hook, with the reserve paying it and its record of the active bin
+    let hooks =
+        State::generate_pair_hooks(ctx.accounts.clone(), &[address], ctx.config.dlmm_program_id)
+            .await?;
+    if let Some(hook) = hooks.get(&address) {
+        keys.extend([
+            hook.reward_token_mint,
+            hook.reward_reserve,
+            hook.bin_array_address(bin_array_index_of(pair.active_id)),
+        ]);
+    }
+    keys.sort();
+    keys.dedup();
+    ctx.accounts.get_multiple_accounts(&keys).await?;
+
+    println!("Recorded pair {} and the accounts its swaps use", address);
+    Ok(())
+}
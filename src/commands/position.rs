@@ -0,0 +1,254 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    app::AppContext,
+    dlmm::{
+        build_add_liquidity_transaction, build_claim_rewards_transactions,
+        build_create_position_transaction, build_remove_liquidity_transaction, RpcSwapSimulator,
+    },
+    state::{LiquidityShape, PositionEntry, PositionRange, PositionResponse},
+};
+
+pub async fn list(ctx: &AppContext, owner: Pubkey, json: bool) -> Result<()> {
+    let positions = ctx.get_positions(owner).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&positions)?);
+        return Ok(());
+    }
+
+    if positions.is_empty() {
+        println!("No position found for {}", owner);
+        return Ok(());
+    }
+
+    for position in &positions {
+        print_position(position);
+    }
+
+    Ok(())
+}
+
+pub async fn show(
+    ctx: &AppContext,
+    address: Pubkey,
+    entry: Option<PositionEntry>,
+    json: bool,
+) -> Result<()> {
+    let position = ctx.get_position(address, entry).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&position)?);
+        return Ok(());
+    }
+
+    print_position(&position);
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    ctx: &AppContext,
+    pair: Pubkey,
+    user: Pubkey,
+    range: PositionRange,
+    amount_x: u64,
+    amount_y: u64,
+    shape: LiquidityShape,
+    json: bool,
+) -> Result<()> {
+    let position =
+        build_create_position_transaction(ctx, pair, user, range, amount_x, amount_y, shape)
+            .await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&position)?);
+        return Ok(());
+    }
+
+    println!(
+        "Position {} (mint {}) on pair {}",
+        position.position, position.position_mint, pair
+    );
+    println!(
+        "  Bins {}..={}, active id {}, shape {:?}",
+        position.lower_bin_id, position.upper_bin_id, position.active_id, position.shape
+    );
+    println!("  {:<10} {:>20} {:>20} {:>20}", "Bin", "Price", "X", "Y");
+    for bin in &position.bins {
+        println!(
+            "  {:<10} {:>20} {:>20} {:>20}",
+            bin.bin_id, bin.price, bin.amount_x, bin.amount_y
+        );
+    }
+    print_transaction(&position.transaction, position.last_valid_block_height);
+
+    Ok(())
+}
+
+pub async fn add(
+    ctx: &AppContext,
+    address: Pubkey,
+    user: Pubkey,
+    amount_x: u64,
+    amount_y: u64,
+    shape: LiquidityShape,
+    json: bool,
+) -> Result<()> {
+    let change =
+        build_add_liquidity_transaction(ctx, address, user, amount_x, amount_y, shape).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&change)?);
+        return Ok(());
+    }
+
+    println!(
+        "Depositing {} X and {} Y into position {}, active id {}",
+        change.amount_x, change.amount_y, change.position, change.active_id
+    );
+    println!(
+        "  {:<10} {:>20} {:>20} {:>20} {:>12}",
+        "Bin", "Price", "X", "Y", "Bin share"
+    );
+    for bin in &change.bins {
+        println!(
+            "  {:<10} {:>20} {:>20} {:>20} {:>11.2}%",
+            bin.bin_id,
+            bin.price,
+            bin.amount_x,
+            bin.amount_y,
+            bin.pool_share * 100.0
+        );
+    }
+    print_transaction(&change.transaction, change.last_valid_block_height);
+
+    Ok(())
+}
+
+pub async fn remove(
+    ctx: &AppContext,
+    address: Pubkey,
+    user: Pubkey,
+    bps: u16,
+    bins: Option<Vec<u32>>,
+    json: bool,
+) -> Result<()> {
+    let change = build_remove_liquidity_transaction(ctx, address, user, bps, bins).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&change)?);
+        return Ok(());
+    }
+
+    println!(
+        "Withdrawing {} X and {} Y from position {}, active id {}",
+        change.amount_x, change.amount_y, change.position, change.active_id
+    );
+    println!("  {:<10} {:>20} {:>20} {:>20}", "Bin", "Price", "X", "Y");
+    for bin in &change.bins {
+        println!(
+            "  {:<10} {:>20} {:>20} {:>20}",
+            bin.bin_id, bin.price, bin.amount_x, bin.amount_y
+        );
+    }
+    print_transaction(&change.transaction, change.last_valid_block_height);
+
+    Ok(())
+}
+
+pub async fn claim(ctx: &AppContext, user: Pubkey, json: bool) -> Result<()> {
+    let simulator = RpcSwapSimulator {
+        rpc_client: ctx.rpc_client.clone(),
+    };
+    let built = build_claim_rewards_transactions(ctx, &simulator, user).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&built)?);
+        return Ok(());
+    }
+
+    println!(
+        "Claiming the rewards of {} positions in {} transactions",
+        built.claims.len(),
+        built.transactions.len()
+    );
+    println!(
+        "  {:<46} {:>20} {:<10} {:>12}",
+        "Position", "Amount", "Token", "Transaction"
+    );
+    for claim in &built.claims {
+        println!(
+            "  {:<46} {:>20} {:<10} {:>12}",
+            claim.position, claim.amount, claim.symbol, claim.transaction
+        );
+    }
+    for transaction in &built.transactions {
+        print_transaction(transaction, built.last_valid_block_height);
+    }
+
+    Ok(())
+}
+
+fn print_transaction(transaction: &str, last_valid_block_height: u64) {
+    println!(
+        "Transaction (valid until block height {}):",
+        last_valid_block_height
+    );
+    println!("{}", transaction);
+}
+
+fn print_position(position: &PositionResponse) {
+    println!(
+        "Position {} on pair {} ({}-{})",
+        position.position_address, position.pair_address, position.symbol_x, position.symbol_y
+    );
+    println!(
+        "  Bins {}..={}, active id {} ({}), price {} {} per {}",
+        position.lower_bin_id,
+        position.upper_bin_id,
+        position.active_id,
+        if position.in_range {
+            "in range"
+        } else {
+            "out of range"
+        },
+        position.price,
+        position.symbol_y,
+        position.symbol_x
+    );
+    println!(
+        "  Holding {} {} and {} {}, earned fees included",
+        position.amount_x, position.symbol_x, position.amount_y, position.symbol_y
+    );
+    println!("  Value {} {}", position.value, position.symbol_y);
+    if let Some(reward) = &position.pending_reward {
+        println!("  Pending rewards {} {}", reward.amount, reward.symbol);
+    }
+    if let Some(pnl) = &position.pnl {
+        println!(
+            "  PnL {} {} since entry, {} {} ({:.2}%) against holding",
+            pnl.pnl, position.symbol_y, pnl.pnl_vs_hold, position.symbol_y, pnl.pnl_vs_hold_pct
+        );
+    }
+
+    println!(
+        "  {:<10} {:>20} {:>20} {:>20}",
+        "Bin", "Price", position.symbol_x, position.symbol_y
+    );
+    for bin in &position.bins {
+        let marker = if bin.bin_id == position.active_id {
+            "*"
+        } else {
+            ""
+        };
+        println!(
+            "  {:<10} {:>20} {:>20} {:>20}",
+            format!("{}{}", bin.bin_id, marker),
+            bin.price,
+            bin.amount_x,
+            bin.amount_y
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+
+use crate::{
+    app::AppContext,
+    cli::QuoteArgs,
+    dlmm::{quote_pair, QuoteInput},
+    state::QuoteSwapMode,
+    utils::parse_ui_amount,
+};
+
+pub async fn run(ctx: &AppContext, args: QuoteArgs) -> Result<()> {
+    let dlmm_client = ctx.get_or_spawn_client(args.pair).await?;
+
+    let pair = dlmm_client.pair_summary().await;
+    let [mint_x_meta, mint_y_meta] = ctx
+        .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+
+    let token_meta = |mint| {
+        [&mint_x_meta, &mint_y_meta]
+            .into_iter()
+            .find(|meta| meta.mint == mint)
+    };
+
+    let (Some(source_meta), Some(destination_meta)) = (
+        token_meta(args.source_mint),
+        token_meta(args.destination_mint),
+    ) else {
+        bail!(
+            "Mints {} and {} are not both traded by pair {}",
+            args.source_mint,
+            args.destination_mint,
+            args.pair
+        );
+    };
+    if source_meta.mint == destination_meta.mint {
+        bail!("Source and destination mints must be different");
+    }
+
+    let fixed_meta = match args.mode {
+        QuoteSwapMode::ExactIn => source_meta,
+        QuoteSwapMode::ExactOut => destination_meta,
+    };
+    let amount = parse_ui_amount(&args.amount, fixed_meta.decimals)?;
+
+    let quote = quote_pair(
+        ctx,
+        &*dlmm_client.saros_dlmm.read().await,
+        &QuoteInput {
+            source_mint: source_meta.mint,
+            destination_mint: destination_meta.mint,
+            amount,
+            swap_mode: args.mode,
+            slippage_bps: args.slippage_bps,
+            verbose: args.verbose,
+        },
+    )
+    .await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&quote)?);
+        return Ok(());
+    }
+
+    let fee_symbol = token_meta(quote.fee_mint.parse()?)
+        .map(|meta| meta.symbol.as_str())
+        .unwrap_or("UNKNOWN");
+
+    println!("{:<14} {}", "Pair", args.pair);
+    println!("{:<14} {} fixed", "Mode", quote.fixed_side);
+    println!(
+        "{:<14} {} {} ({})",
+        "In", quote.in_amount_ui, source_meta.symbol, quote.in_amount
+    );
+    println!(
+        "{:<14} {} {} ({})",
+        "Out", quote.out_amount_ui, destination_meta.symbol, quote.out_amount
+    );
+    println!(
+        "{:<14} {} {} ({})",
+        "Fee", quote.fee_amount_ui, fee_symbol, quote.fee_amount
+    );
+    match args.mode {
+        QuoteSwapMode::ExactIn => println!(
+            "{:<14} {} ({} bps slippage)",
+            "Min received", quote.min_out_amount, quote.slippage_bps
+        ),
+        QuoteSwapMode::ExactOut => println!(
+            "{:<14} {} ({} bps slippage)",
+            "Max spent", quote.max_in_amount, quote.slippage_bps
+        ),
+    }
+    println!(
+        "{:<14} {} {} per {}",
+        "Spot price", quote.spot_price, destination_meta.symbol, source_meta.symbol
+    );
+    println!(
+        "{:<14} {} {} per {}",
+        "Exec. price", quote.execution_price, destination_meta.symbol, source_meta.symbol
+    );
+    println!("{:<14} {:.2} bps", "Price impact", quote.price_impact_bps);
+
+    if let Some(path) = &quote.path {
+        println!();
+        println!(
+            "{:<10} {:>20} {:>20} {:>20} {:>20}",
+            "Bin", "Price", "In", "Out", "Fee"
+        );
+        for step in &path.steps {
+            println!(
+                "{:<10} {:>20} {:>20} {:>20} {:>20}",
+                step.bin_id, step.price, step.amount_in, step.amount_out, step.fee
+            );
+        }
+        println!(
+            "Active bin {} -> {}",
+            path.start_active_id, path.final_active_id
+        );
+        if path.amount_left > 0 {
+            println!(
+                "{} could not be swapped within the loaded bin arrays",
+                path.amount_left
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use anyhow::{bail, Context, Result};
+use futures_util::{stream::select_all, StreamExt};
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::watch, time::Instant};
+use tracing::{info, warn};
+
+use jupiter_amm_interface::Amm;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use crate::{
+    app::AppContext,
+    dlmm::{AccountMap, DLMMClient},
+};
+
+/// How long a pair is polled after its websocket drops before subscribing again
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(10);
+
+/// A live pair: the shared client and a receiver that ticks on every applied change
+pub struct FeedSubscription {
+    pub client: Arc<DLMMClient>,
+    pub updates: watch::Receiver<u64>,
+}
+
+/// Returns the registered client of the pair, kept up to date through `accountSubscribe` on the
+/// accounts the AMM depends on, so requests read the latest state without fetching it themselves.
+/// The subscriptions are started on first use and stop when the client is evicted.
+pub async fn subscribe_pair(ctx: &AppContext, pair_key: Pubkey) -> Result<FeedSubscription> {
+    ctx.get_or_spawn_client(pair_key).await?;
+
+    let (client, updates) = ctx
+        .dlmm_clients
+        .watch(pair_key, |client, version| {
+            info!("📡 Subscribing to accounts of pair: {:?}", pair_key);
+            tokio::spawn(run(ctx.clone(), pair_key, client, version)).abort_handle()
+        })
+        .await
+        .context("DLMM client was evicted before it could be subscribed")?;
+
+    Ok(FeedSubscription { client, updates })
+}
+
+pub async fn live_client(ctx: &AppContext, pair_key: Pubkey) -> Result<Arc<DLMMClient>> {
+    Ok(subscribe_pair(ctx, pair_key).await?.client)
+}
+
+/// Streams account changes into the client, polling while the websocket is unavailable
+async fn run(
+    ctx: AppContext,
+    pair_key: Pubkey,
+    client: Arc<DLMMClient>,
+    version: watch::Sender<u64>,
+) {
+    if !ctx.accounts.is_live() {
+        info!(
+            "Accounts of pair {:?} are static, nothing to subscribe to",
+            pair_key
+        );
+        return;
+    }
+
+    loop {
+        match stream_updates(&ctx, &client, &version).await {
+            Ok(()) => info!(
+                "🔁 Active bin array moved, resubscribing pair: {:?}",
+                pair_key
+            ),
+            Err(e) => {
+                warn!(
+                    "⚠️ Account subscription for pair {:?} dropped, polling instead: {}",
+                    pair_key, e
+                );
+                poll_updates(&ctx, pair_key, &client, &version, RESUBSCRIBE_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Subscribes to every account the AMM depends on and applies each change as it arrives.
+/// Returns once the set of accounts changes, i.e. the active bin crossed into another bin array.
+async fn stream_updates(
+    ctx: &AppContext,
+    client: &DLMMClient,
+    version: &watch::Sender<u64>,
+) -> Result<()> {
+    let pubsub = PubsubClient::new(&ctx.config.ws_url).await?;
+    let subscribed = client.saros_dlmm.read().await.get_accounts_to_update();
+
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+    let mut streams = vec![];
+    let mut unsubscribes = vec![];
+    for address in subscribed.iter().copied() {
+        let (stream, unsubscribe) = pubsub
+            .account_subscribe(&address, Some(config.clone()))
+            .await?;
+        streams.push(stream.map(move |response| (address, response)));
+        unsubscribes.push(unsubscribe);
+    }
+    let mut updates = select_all(streams);
+
+    let result: Result<()> = async {
+        // Catch up on anything that changed before the subscriptions were in place
+        let mut accounts = client.fetch_accounts(ctx).await?;
+        client.apply(&accounts).await?;
+        version.send_modify(|v| *v += 1);
+
+        while let Some((address, response)) = updates.next().await {
+            let Some(account) = response.value.decode::<Account>() else {
+                warn!("⚠️ Failed to decode account update for {:?}", address);
+                continue;
+            };
+            ctx.accounts
+                .observe(&address, &account, response.context.slot)
+                .await;
+
+            // The AMM expects every account it depends on, so the whole map is applied again
+            accounts.insert(address, account);
+            client.apply(&accounts).await?;
+            version.send_modify(|v| *v += 1);
+
+            if client.saros_dlmm.read().await.get_accounts_to_update() != subscribed {
+                return Ok(());
+            }
+        }
+
+        bail!("Account subscription stream ended")
+    }
+    .await;
+
+    drop(updates);
+    for unsubscribe in unsubscribes {
+        unsubscribe().await;
+    }
+    if let Err(e) = pubsub.shutdown().await {
+        warn!("⚠️ Failed to close pubsub client: {}", e);
+    }
+
+    result
+}
+
+/// Polls the pair's accounts for `duration`, applying them whenever one of them changes
+async fn poll_updates(
+    ctx: &AppContext,
+    pair_key: Pubkey,
+    client: &DLMMClient,
+    version: &watch::Sender<u64>,
+    duration: Duration,
+) {
+    let deadline = Instant::now() + duration;
+    let mut interval = tokio::time::interval(ctx.config.quote_feed_interval);
+    let mut last_accounts: Option<AccountMap> = None;
+
+    while Instant::now() < deadline {
+        interval.tick().await;
+
+        let accounts = match client.fetch_accounts(ctx).await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                warn!("⚠️ Failed to poll accounts for pair {:?}: {}", pair_key, e);
+                continue;
+            }
+        };
+
+        if last_accounts.as_ref() == Some(&accounts) {
+            continue;
+        }
+
+        if let Err(e) = client.apply(&accounts).await {
+            warn!("⚠️ Failed to update DLMM client {:?}: {}", pair_key, e);
+            continue;
+        }
+
+        last_accounts = Some(accounts);
+        version.send_modify(|v| *v += 1);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{
+    sync::{watch, Mutex},
+    task::AbortHandle,
+    time::Instant,
+};
+use tracing::info;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::dlmm::DLMMClient;
+
+struct LiveSubscription {
+    version: watch::Sender<u64>,
+    task: AbortHandle,
+}
+
+struct RegistryEntry {
+    client: Arc<DLMMClient>,
+    last_used: Instant,
+    subscription: Option<LiveSubscription>,
+}
+
+impl RegistryEntry {
+    /// Entries streamed to a WebSocket client are kept regardless of TTL and LRU
+    fn has_subscribers(&self) -> bool {
+        self.subscription
+            .as_ref()
+            .is_some_and(|subscription| subscription.version.receiver_count() > 0)
+    }
+}
+
+/// Long-lived clients keyed by pair. An entry is evicted once it has been idle for longer than
+/// `idle_ttl`, or when it is the least recently used one and the registry is full.
+#[derive(Clone)]
+pub struct ClientRegistry {
+    entries: Arc<Mutex<HashMap<Pubkey, RegistryEntry>>>,
+    idle_ttl: Duration,
+    capacity: usize,
+}
+
+impl ClientRegistry {
+    pub fn new(idle_ttl: Duration, capacity: usize) -> Self {
+        ClientRegistry {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            idle_ttl,
+            capacity,
+        }
+    }
+
+    /// Returns the registered client of the pair and whether account subscriptions keep it live
+    pub async fn get(&self, pair_key: Pubkey) -> Option<(Arc<DLMMClient>, bool)> {
+        let mut entries = self.entries.lock().await;
+        self.evict(&mut entries, None);
+
+        let entry = entries.get_mut(&pair_key)?;
+        entry.last_used = Instant::now();
+        Some((entry.client.clone(), entry.subscription.is_some()))
+    }
+
+    /// Registers the client, unless another request registered one for the pair first.
+    /// Either way the registered client is returned.
+    pub async fn insert(&self, pair_key: Pubkey, client: Arc<DLMMClient>) -> Arc<DLMMClient> {
+        let mut entries = self.entries.lock().await;
+        self.evict(&mut entries, Some(pair_key));
+
+        let entry = entries.entry(pair_key).or_insert_with(|| RegistryEntry {
+            client,
+            last_used: Instant::now(),
+            subscription: None,
+        });
+        entry.last_used = Instant::now();
+        entry.client.clone()
+    }
+
+    /// Subscribes to the updates of a registered pair, starting its subscription task with
+    /// `start` if none is running yet
+    pub async fn watch(
+        &self,
+        pair_key: Pubkey,
+        start: impl FnOnce(Arc<DLMMClient>, watch::Sender<u64>) -> AbortHandle,
+    ) -> Option<(Arc<DLMMClient>, watch::Receiver<u64>)> {
+        let mut entries = self.entries.lock().await;
+        let entry = entries.get_mut(&pair_key)?;
+        entry.last_used = Instant::now();
+
+        let client = entry.client.clone();
+        let subscription = entry.subscription.get_or_insert_with(|| {
+            let (version, _) = watch::channel(0);
+            LiveSubscription {
+                task: start(client.clone(), version.clone()),
+                version,
+            }
+        });
+
+        Some((client, subscription.version.subscribe()))
+    }
+
+    /// Drops idle entries, then the least recently used ones until there is room for `incoming`
+    fn evict(&self, entries: &mut HashMap<Pubkey, RegistryEntry>, incoming: Option<Pubkey>) {
+        let expired: Vec<Pubkey> = entries
+            .iter()
+            .filter(|(_, entry)| {
+                !entry.has_subscribers() && entry.last_used.elapsed() > self.idle_ttl
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            remove_entry(entries, &key, "idle");
+        }
+
+        let is_new = incoming.is_some_and(|key| !entries.contains_key(&key));
+        while is_new && entries.len() >= self.capacity {
+            let least_recently_used = entries
+                .iter()
+                .filter(|(_, entry)| !entry.has_subscribers())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+
+            match least_recently_used {
+                Some(key) => remove_entry(entries, &key, "least recently used"),
+                None => break,
+            }
+        }
+    }
+}
+
+fn remove_entry(entries: &mut HashMap<Pubkey, RegistryEntry>, pair_key: &Pubkey, reason: &str) {
+    if let Some(entry) = entries.remove(pair_key) {
+        if let Some(subscription) = entry.subscription {
+            subscription.task.abort();
+        }
+        info!("🧹 Evicted {} DLMMClient for pool: {:?}", reason, pair_key);
+    }
+}
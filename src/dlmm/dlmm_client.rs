@@ -1,47 +1,127 @@
-use anyhow::Result;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use anyhow::{Context, Result};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::Instant};
+use tracing::warn;
 
 use ahash::RandomState;
 use jupiter_amm_interface::Amm;
 use saros_dlmm::SarosDlmm;
 use solana_sdk::{account::Account, pubkey::Pubkey};
 
-use crate::app::AppContext;
+use crate::{app::AppContext, error::AppError, state::PairSummary};
+
+/// Accounts keyed by address, as consumed by `Amm::update`
+pub type AccountMap = HashMap<Pubkey, Account, RandomState>;
 
 pub struct DLMMClient {
     pub saros_dlmm: Arc<RwLock<SarosDlmm>>,
+    /// Last time the AMM state was brought up to date
+    pub updated_at: RwLock<Instant>,
+}
+
+/// Fetches the current state of `keys`, leaving out the accounts that do not exist
+async fn fetch_account_map(ctx: &AppContext, keys: Vec<Pubkey>) -> Result<AccountMap> {
+    let accounts = ctx
+        .accounts
+        .get_multiple_accounts(&keys)
+        .await
+        .context("Failed to get AMM accounts")?;
+
+    Ok(keys
+        .into_iter()
+        .zip(accounts)
+        .fold(AccountMap::default(), |mut m, (address, account)| {
+            if let Some(account) = account {
+                m.insert(address, account);
+            }
+            m
+        }))
+}
+
+fn apply_account_map(amm: &mut SarosDlmm, account_map: &AccountMap) -> Result<()> {
+    amm.update(account_map)
+        .map_err(|e| AppError::DecodeFailure(format!("AMM accounts: {}", e)))?;
+    Ok(())
 }
 
-pub trait UpdateAmm: Amm {
-    async fn update_amm(&mut self, ctx: &AppContext) -> Result<()>;
+/// Rounds of fetching an update can take. The pair comes first, then the bin arrays around its
+/// active bin, which only move again if the active bin crossed into another array meanwhile.
+const MAX_UPDATE_ROUNDS: usize = 4;
+
+pub trait UpdateAmm {
+    async fn update_amm(&self, ctx: &AppContext) -> Result<()>;
 }
 
-impl UpdateAmm for SarosDlmm {
-    async fn update_amm(&mut self, ctx: &AppContext) -> Result<()> {
-        let accounts_to_update = self.get_accounts_to_update();
-        let account_map: HashMap<Pubkey, Account, RandomState> = ctx
-            .rpc_client
-            .get_multiple_accounts(&accounts_to_update)
-            .unwrap()
-            .into_iter()
-            .zip(accounts_to_update)
-            .fold(HashMap::default(), |mut m, (account, address)| {
-                if let Some(account) = account {
-                    m.insert(address, account);
-                }
-                m
-            });
-        self.update(&account_map).unwrap();
+impl UpdateAmm for RwLock<SarosDlmm> {
+    /// Updates the AMM in place, fetching again as long as what it applied changes the accounts
+    /// it depends on. The lock is only taken to read those and to apply them, quotes keep being
+    /// served during the round trips.
+    async fn update_amm(&self, ctx: &AppContext) -> Result<()> {
+        let mut accounts_to_update = self.read().await.get_accounts_to_update();
+        for _ in 0..MAX_UPDATE_ROUNDS {
+            let account_map = fetch_account_map(ctx, accounts_to_update.clone()).await?;
+
+            let mut amm = self.write().await;
+            apply_account_map(&mut amm, &account_map)?;
+            let next_accounts = amm.get_accounts_to_update();
+            drop(amm);
 
+            if next_accounts == accounts_to_update {
+                return Ok(());
+            }
+            accounts_to_update = next_accounts;
+        }
+
+        warn!(
+            "⚠️ AMM accounts still changing after {} updates",
+            MAX_UPDATE_ROUNDS
+        );
         Ok(())
     }
 }
 
 impl DLMMClient {
-    pub async fn update(&self, ctx: &AppContext) -> Result<()> {
-        let mut s = self.saros_dlmm.write().await;
-        s.update_amm(ctx).await?;
+    pub fn new(saros_dlmm: SarosDlmm) -> Self {
+        DLMMClient {
+            saros_dlmm: Arc::new(RwLock::new(saros_dlmm)),
+            updated_at: RwLock::new(Instant::now()),
+        }
+    }
+
+    pub async fn is_stale(&self, ttl: Duration) -> bool {
+        self.updated_at.read().await.elapsed() > ttl
+    }
+
+    /// Copies the pair out of the AMM, for requests that go on to do I/O without holding its lock
+    pub async fn pair_summary(&self) -> PairSummary {
+        let saros_dlmm = self.saros_dlmm.read().await;
+        let pair = &saros_dlmm.pair;
+        PairSummary {
+            address: saros_dlmm.key(),
+            token_mint_x: pair.token_mint_x,
+            token_mint_y: pair.token_mint_y,
+            bin_step: pair.bin_step,
+            active_id: pair.active_id,
+        }
+    }
+
+    /// Brings the pair and its bin arrays up to date before quoting
+    pub async fn refresh(&self, ctx: &AppContext) -> Result<()> {
+        self.saros_dlmm.update_amm(ctx).await?;
+        *self.updated_at.write().await = Instant::now();
+        Ok(())
+    }
+
+    /// Fetches the current state of every account the AMM depends on
+    pub async fn fetch_accounts(&self, ctx: &AppContext) -> Result<AccountMap> {
+        let accounts_to_update = self.saros_dlmm.read().await.get_accounts_to_update();
+        fetch_account_map(ctx, accounts_to_update).await
+    }
+
+    /// Applies already fetched accounts to the AMM
+    pub async fn apply(&self, account_map: &AccountMap) -> Result<()> {
+        apply_account_map(&mut *self.saros_dlmm.write().await, account_map)?;
+        *self.updated_at.write().await = Instant::now();
         Ok(())
     }
 }
@@ -0,0 +1,32 @@
+use saros_sdk::state::pair::Pair;
+
+/// Fee rates are expressed in parts of `FEE_PRECISION`
+pub const FEE_PRECISION: u64 = 1_000_000_000;
+
+/// Static part of the swap fee, `base_factor * bin_step * 10`
+pub fn base_fee(pair: &Pair) -> u64 {
+    pair.static_fee_parameters.base_factor as u64 * pair.bin_step as u64 * 10
+}
+
+/// Volatility part of the swap fee for the given volatility accumulator
+pub fn variable_fee(pair: &Pair, volatility_accumulator: u32) -> u64 {
+    let variable_fee_control = pair.static_fee_parameters.variable_fee_control as u128;
+    if variable_fee_control == 0 {
+        return 0;
+    }
+
+    let square_vfa_bin = (volatility_accumulator as u128 * pair.bin_step as u128).pow(2);
+    ((square_vfa_bin * variable_fee_control + 99_999_999_999) / 100_000_000_000) as u64
+}
+
+/// Total swap fee rate at the pair's current volatility, capped at 10%
+pub fn total_fee(pair: &Pair) -> u64 {
+    let fee =
+        base_fee(pair) + variable_fee(pair, pair.dynamic_fee_parameters.volatility_accumulator);
+    fee.min(FEE_PRECISION / 10)
+}
+
+/// Converts a fee rate into basis points
+pub fn fee_to_bps(fee: u64) -> f64 {
+    fee as f64 * 10_000.0 / FEE_PRECISION as f64
+}
@@ -0,0 +1,1278 @@
+use std::collections::HashMap;
+
+use anyhow::{ensure, Context, Result};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use saros_sdk::{state::bin_array::BinArray, utils::helper::get_pair_bin_array};
+use serde::Serialize;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account_client::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::native_mint;
+
+use crate::{
+    app::AppContext,
+    dlmm::{
+        has_wrapped_sol_account, live_client, token_account_amount, unsigned_transaction,
+        wrapped_sol_account,
+    },
+    error::AppError,
+    pricing::{id_from_ui_price, price_from_id, to_ui_price, ui_price_from_id, Rounding},
+    state::{
+        bin_array_index_of, pair_vault_address, position_address, position_bin_amounts, share_of,
+        LiquidityShape, PairSummary, Position, PositionBinAmounts, PositionRange, State,
+        BIN_ARRAY_SIZE, POSITION_MAX_BINS,
+    },
+    utils::anchor_discriminator,
+};
+
+const BPS: u16 = 10_000;
+
+/// Share of each token deposited into one bin, in basis points of the token amount
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BinLiquidityDistribution {
+    /// Bin id relative to the active bin
+    pub relative_bin_id: i32,
+    pub distribution_x: u16,
+    pub distribution_y: u16,
+}
+
+fn shape_weight(shape: LiquidityShape, distance: u32, width: u32) -> f64 {
+    match shape {
+        LiquidityShape::Spot => 1.0,
+        LiquidityShape::Curve => {
+            let sigma = (width as f64 / 4.0).max(1.0);
+            (-(distance as f64).powi(2) / (2.0 * sigma * sigma)).exp()
+        }
+        LiquidityShape::BidAsk => distance as f64 + 1.0,
+    }
+}
+
+/// Scales weights to basis points summing to exactly 10_000, the rounding leftovers going to
+/// the heaviest bin. All zeros when there is no weight at all.
+fn weights_to_bps(weights: &[f64]) -> Vec<u16> {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return vec![0; weights.len()];
+    }
+
+    let mut bps: Vec<u16> = weights
+        .iter()
+        .map(|weight| (weight / total * BPS as f64).floor() as u16)
+        .collect();
+    let leftover = BPS - bps.iter().sum::<u16>();
+    let heaviest = weights
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i);
+    if let Some(i) = heaviest {
+        bps[i] += leftover;
+    }
+
+    bps
+}
+
+/// Spreads X over the bins from the active one up and Y over the bins from the active one down,
+/// weighted by `shape`. A token with no bin on its side of the range gets no distribution.
+pub fn distribute_liquidity(
+    shape: LiquidityShape,
+    active_id: u32,
+    lower_bin_id: u32,
+    upper_bin_id: u32,
+) -> Vec<BinLiquidityDistribution> {
+    let width = upper_bin_id - lower_bin_id + 1;
+    let bin_ids: Vec<u32> = (lower_bin_id..=upper_bin_id).collect();
+    let side_weights = |on_side: fn(u32, u32) -> bool| -> Vec<f64> {
+        bin_ids
+            .iter()
+            .map(|&id| {
+                if on_side(id, active_id) {
+                    shape_weight(shape, id.abs_diff(active_id), width)
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    };
+
+    let distribution_x = weights_to_bps(&side_weights(|id, active_id| id >= active_id));
+    let distribution_y = weights_to_bps(&side_weights(|id, active_id| id <= active_id));
+
+    bin_ids
+        .iter()
+        .zip(distribution_x.into_iter().zip(distribution_y))
+        .map(
+            |(&id, (distribution_x, distribution_y))| BinLiquidityDistribution {
+                relative_bin_id: (id as i64 - active_id as i64) as i32,
+                distribution_x,
+                distribution_y,
+            },
+        )
+        .collect()
+}
+
+/// Anchor programs emitting events through CPI expect this PDA and the program itself last
+pub fn event_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], program_id).0
+}
+
+fn event_accounts(program_id: &Pubkey) -> [AccountMeta; 2] {
+    [
+        AccountMeta::new_readonly(event_authority(program_id), false),
+        AccountMeta::new_readonly(*program_id, false),
+    ]
+}
+
+/// The position NFT is a Token-2022 mint, held in the owner's associated token account
+pub fn position_token_account(owner: &Pubkey, position_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, position_mint, &spl_token_2022::ID)
+}
+
+/// The two bin arrays a position spans, the one holding its lower bin and the next one
+pub fn position_bin_arrays(
+    pair_key: &Pubkey,
+    program_id: &Pubkey,
+    lower_bin_id: u32,
+) -> (Pubkey, Pubkey) {
+    get_pair_bin_array(bin_array_index_of(lower_bin_id), pair_key, program_id)
+}
+
+pub fn initialize_bin_array_instruction(
+    program_id: &Pubkey,
+    pair_key: &Pubkey,
+    user: &Pubkey,
+    index: u32,
+) -> Instruction {
+    let bin_array = get_pair_bin_array(index, pair_key, program_id).0;
+
+    let mut data = anchor_discriminator("global:initialize_bin_array").to_vec();
+    data.extend_from_slice(&index.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pair_key, false),
+            AccountMeta::new(bin_array, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    }
+}
+
+/// Opens an empty position over `lower_bin_id..=upper_bin_id` and mints its NFT to `user`.
+/// The program takes the range relative to the active bin.
+pub fn create_position_instruction(
+    program_id: &Pubkey,
+    pair: &PairSummary,
+    user: &Pubkey,
+    position_mint: &Pubkey,
+    lower_bin_id: u32,
+    upper_bin_id: u32,
+) -> Instruction {
+    let (bin_array_lower, bin_array_upper) =
+        position_bin_arrays(&pair.address, program_id, lower_bin_id);
+    let relative_bin_id = |id: u32| (id as i64 - pair.active_id as i64) as i32;
+
+    let mut data = anchor_discriminator("global:create_position").to_vec();
+    data.extend_from_slice(&relative_bin_id(lower_bin_id).to_le_bytes());
+    data.extend_from_slice(&relative_bin_id(upper_bin_id).to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(pair.address, false),
+        AccountMeta::new(position_address(position_mint, program_id), false),
+        AccountMeta::new(*position_mint, true),
+        AccountMeta::new(position_token_account(user, position_mint), false),
+        AccountMeta::new_readonly(bin_array_lower, false),
+        AccountMeta::new_readonly(bin_array_upper, false),
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(spl_token_2022::ID, false),
+        AccountMeta::new_readonly(spl_associated_token_account_client::program::ID, false),
+    ];
+    accounts.extend(event_accounts(program_id));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Accounts shared by the instructions moving liquidity in and out of a position
+pub struct PositionLiquidityAccounts {
+    pub program_id: Pubkey,
+    pub pair_key: Pubkey,
+    pub token_mint_x: Pubkey,
+    pub token_mint_y: Pubkey,
+    pub token_program_x: Pubkey,
+    pub token_program_y: Pubkey,
+    pub user: Pubkey,
+    pub position_mint: Pubkey,
+    pub lower_bin_id: u32,
+    /// Whether the user already holds a wrapped SOL account, which is then left open.
+    /// Only an account the transaction creates is closed to unwrap SOL.
+    pub wrapped_sol_exists: bool,
+}
+
+impl PositionLiquidityAccounts {
+    /// Resolves the token program of both mints of the pair, and for SOL pairs whether the user
+    /// already has a wrapped SOL account
+    pub async fn load(
+        ctx: &AppContext,
+        pair: &PairSummary,
+        user: Pubkey,
+        position_mint: Pubkey,
+        lower_bin_id: u32,
+    ) -> Result<Self> {
+        let (mint_account_x, mint_account_y) = tokio::try_join!(
+            ctx.get_mint_account(pair.token_mint_x),
+            ctx.get_mint_account(pair.token_mint_y)
+        )?;
+        let wrapped_sol_exists = (pair.token_mint_x == native_mint::ID
+            || pair.token_mint_y == native_mint::ID)
+            && has_wrapped_sol_account(ctx, &user).await?;
+
+        Ok(PositionLiquidityAccounts {
+            program_id: ctx.config.dlmm_program_id,
+            pair_key: pair.address,
+            token_mint_x: pair.token_mint_x,
+            token_mint_y: pair.token_mint_y,
+            token_program_x: mint_account_x.account.owner,
+            token_program_y: mint_account_y.account.owner,
+            user,
+            position_mint,
+            lower_bin_id,
+            wrapped_sol_exists,
+        })
+    }
+
+    fn account_metas(&self) -> Vec<AccountMeta> {
+        let (bin_array_lower, bin_array_upper) =
+            position_bin_arrays(&self.pair_key, &self.program_id, self.lower_bin_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(self.pair_key, false),
+            AccountMeta::new(
+                position_address(&self.position_mint, &self.program_id),
+                false,
+            ),
+            AccountMeta::new(bin_array_lower, false),
+            AccountMeta::new(bin_array_upper, false),
+            AccountMeta::new_readonly(self.token_mint_x, false),
+            AccountMeta::new_readonly(self.token_mint_y, false),
+            AccountMeta::new(
+                pair_vault_address(&self.pair_key, &self.token_mint_x, &self.token_program_x),
+                false,
+            ),
+            AccountMeta::new(
+                pair_vault_address(&self.pair_key, &self.token_mint_y, &self.token_program_y),
+                false,
+            ),
+            AccountMeta::new(
+                get_associated_token_address_with_program_id(
+                    &self.user,
+                    &self.token_mint_x,
+                    &self.token_program_x,
+                ),
+                false,
+            ),
+            AccountMeta::new(
+                get_associated_token_address_with_program_id(
+                    &self.user,
+                    &self.token_mint_y,
+                    &self.token_program_y,
+                ),
+                false,
+            ),
+            AccountMeta::new_readonly(
+                position_token_account(&self.user, &self.position_mint),
+                false,
+            ),
+            AccountMeta::new_readonly(self.token_program_x, false),
+            AccountMeta::new_readonly(self.token_program_y, false),
+            AccountMeta::new_readonly(spl_token_2022::ID, false),
+            AccountMeta::new(self.user, true),
+        ];
+        accounts.extend(event_accounts(&self.program_id));
+        accounts
+    }
+
+    /// The program expects both token accounts even when only one side moves
+    fn create_token_accounts(&self) -> Vec<Instruction> {
+        [
+            (self.token_mint_x, self.token_program_x),
+            (self.token_mint_y, self.token_program_y),
+        ]
+        .iter()
+        .map(|(mint, token_program)| {
+            create_associated_token_account_idempotent(&self.user, &self.user, mint, token_program)
+        })
+        .collect()
+    }
+
+    /// The amount moved on the native SOL side, when one of the tokens is SOL
+    pub fn native_amount(&self, amount_x: u64, amount_y: u64) -> Option<u64> {
+        [(self.token_mint_x, amount_x), (self.token_mint_y, amount_y)]
+            .into_iter()
+            .find(|(mint, _)| *mint == native_mint::ID)
+            .map(|(_, amount)| amount)
+    }
+}
+
+/// Deposits `amount_x` and `amount_y` into the position, split across its bins by `distributions`
+pub fn increase_position_instruction(
+    accounts: &PositionLiquidityAccounts,
+    amount_x: u64,
+    amount_y: u64,
+    distributions: &[BinLiquidityDistribution],
+) -> Instruction {
+    let mut data = anchor_discriminator("global:increase_position").to_vec();
+    data.extend_from_slice(&amount_x.to_le_bytes());
+    data.extend_from_slice(&amount_y.to_le_bytes());
+    data.extend_from_slice(&(distributions.len() as u32).to_le_bytes());
+    for distribution in distributions {
+        data.extend_from_slice(&distribution.relative_bin_id.to_le_bytes());
+        data.extend_from_slice(&distribution.distribution_x.to_le_bytes());
+        data.extend_from_slice(&distribution.distribution_y.to_le_bytes());
+    }
+
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: accounts.account_metas(),
+        data,
+    }
+}
+
+/// Burns `shares` of the position's liquidity, one entry per bin from its lower bin, and sends
+/// the matching tokens back to the user
+pub fn decrease_position_instruction(
+    accounts: &PositionLiquidityAccounts,
+    shares: &[u128],
+) -> Instruction {
+    let mut data = anchor_discriminator("global:decrease_position").to_vec();
+    data.extend_from_slice(&(shares.len() as u32).to_le_bytes());
+    for share in shares {
+        data.extend_from_slice(&share.to_le_bytes());
+    }
+
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: accounts.account_metas(),
+        data,
+    }
+}
+
+/// Moves `amount` lamports into the user's existing wrapped SOL account so it can be deposited
+pub fn wrap_sol_instructions(user: &Pubkey, amount: u64) -> Result<Vec<Instruction>> {
+    let token_account = wrapped_sol_account(user);
+
+    Ok(vec![
+        system_instruction::transfer(user, &token_account, amount),
+        spl_token::instruction::sync_native(&spl_token::ID, &token_account)?,
+    ])
+}
+
+/// Closes the user's wrapped SOL account, returning its whole balance as native SOL. Only for
+/// an account the same transaction created.
+pub fn unwrap_sol_instruction(user: &Pubkey) -> Result<Instruction> {
+    let token_account = wrapped_sol_account(user);
+
+    Ok(spl_token::instruction::close_account(
+        &spl_token::ID,
+        &token_account,
+        user,
+        user,
+        &[],
+    )?)
+}
+
+/// The deposit surrounded by the token account creation and the SOL wrapping it needs
+fn deposit_instructions(
+    accounts: &PositionLiquidityAccounts,
+    amount_x: u64,
+    amount_y: u64,
+    distributions: &[BinLiquidityDistribution],
+) -> Result<Vec<Instruction>> {
+    let mut instructions = accounts.create_token_accounts();
+
+    let native_amount = accounts.native_amount(amount_x, amount_y);
+    if let Some(amount) = native_amount.filter(|amount| *amount > 0) {
+        instructions.extend(wrap_sol_instructions(&accounts.user, amount)?);
+    }
+    instructions.push(increase_position_instruction(
+        accounts,
+        amount_x,
+        amount_y,
+        distributions,
+    ));
+    if native_amount.is_some() && !accounts.wrapped_sol_exists {
+        instructions.push(unwrap_sol_instruction(&accounts.user)?);
+    }
+
+    Ok(instructions)
+}
+
+/// The withdrawal, with SOL unwrapped once received unless the user keeps a wrapped SOL account
+fn withdraw_instructions(
+    accounts: &PositionLiquidityAccounts,
+    shares: &[u128],
+) -> Result<Vec<Instruction>> {
+    let mut instructions = accounts.create_token_accounts();
+
+    instructions.push(decrease_position_instruction(accounts, shares));
+    if accounts.native_amount(0, 0).is_some() && !accounts.wrapped_sol_exists {
+        instructions.push(unwrap_sol_instruction(&accounts.user)?);
+    }
+
+    Ok(instructions)
+}
+
+/// Each token can only go to its side of the active bin, fails when that side is out of the range
+fn ensure_deposit_fits(
+    distributions: &[BinLiquidityDistribution],
+    amount_x: u64,
+    amount_y: u64,
+) -> Result<()> {
+    ensure!(
+        amount_x > 0 || amount_y > 0,
+        AppError::InvalidInput("Nothing to deposit".to_string())
+    );
+    ensure!(
+        amount_x == 0 || distributions.iter().any(|d| d.distribution_x > 0),
+        AppError::InvalidInput(
+            "X can only be deposited from the active bin up, the range is below it".to_string()
+        )
+    );
+    ensure!(
+        amount_y == 0 || distributions.iter().any(|d| d.distribution_y > 0),
+        AppError::InvalidInput(
+            "Y can only be deposited from the active bin down, the range is above it".to_string()
+        )
+    );
+
+    Ok(())
+}
+
+/// Compiles the instructions for `user` with a fresh blockhash
+async fn latest_unsigned_transaction(
+    ctx: &AppContext,
+    user: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<(VersionedTransaction, u64)> {
+    let (blockhash, last_valid_block_height) = ctx
+        .rpc_client
+        .get_latest_blockhash_with_commitment(ctx.rpc_client.commitment())
+        .await?;
+
+    Ok((
+        unsigned_transaction(user, instructions, blockhash)?,
+        last_valid_block_height,
+    ))
+}
+
+/// Where a deposit goes, amounts are raw and rounded down like the program does
+#[derive(Debug, Clone, Serialize)]
+pub struct BinDeposit {
+    pub bin_id: u32,
+    /// Price of one X in Y, adjusted for decimals
+    pub price: f64,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    /// Share of the bin the position owns after the deposit, both tokens valued at the bin price
+    pub pool_share: f64,
+}
+
+/// What a withdrawal returns from one bin, raw and rounded down like the program does
+#[derive(Debug, Clone, Serialize)]
+pub struct BinWithdrawal {
+    pub bin_id: u32,
+    /// Price of one X in Y, adjusted for decimals
+    pub price: f64,
+    /// Liquidity shares burnt
+    pub liquidity_share: String,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Splits the deposit across the bins from the current reserves. `holdings` are the tokens the
+/// position already has in each bin, empty for a new position.
+fn deposit_preview(
+    pair: &PairSummary,
+    decimals: (u8, u8),
+    distributions: &[BinLiquidityDistribution],
+    amount_x: u64,
+    amount_y: u64,
+    holdings: &[PositionBinAmounts],
+    bin_arrays: &HashMap<u32, BinArray>,
+) -> Vec<BinDeposit> {
+    let portion = |amount: u64, bps: u16| (amount as u128 * bps as u128 / BPS as u128) as u64;
+
+    distributions
+        .iter()
+        .map(|distribution| {
+            let bin_id = (pair.active_id as i64 + distribution.relative_bin_id as i64) as u32;
+            let raw_price = price_from_id(pair.bin_step, bin_id);
+            let value = |x: u64, y: u64| x as f64 * raw_price + y as f64;
+
+            let deposit_x = portion(amount_x, distribution.distribution_x);
+            let deposit_y = portion(amount_y, distribution.distribution_y);
+            let reserves = bin_arrays
+                .get(&bin_array_index_of(bin_id))
+                .map(|bin_array| &bin_array.bins[(bin_id % BIN_ARRAY_SIZE) as usize])
+                .map_or(0.0, |bin| value(bin.reserve_x, bin.reserve_y));
+            let held = holdings
+                .iter()
+                .find(|holding| holding.bin_id == bin_id)
+                .map_or(0.0, |holding| value(holding.amount_x, holding.amount_y));
+            let deposit = value(deposit_x, deposit_y);
+
+            BinDeposit {
+                bin_id,
+                price: to_ui_price(raw_price, decimals.0, decimals.1),
+                amount_x: deposit_x,
+                amount_y: deposit_y,
+                pool_share: if reserves + deposit > 0.0 {
+                    (held + deposit) / (reserves + deposit)
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuiltPosition {
+    /// Base64 encoded v0 transaction, already signed by the new position mint
+    pub transaction: String,
+    pub last_valid_block_height: u64,
+    pub position: String,
+    pub position_mint: String,
+    pub lower_bin_id: u32,
+    pub upper_bin_id: u32,
+    pub active_id: u32,
+    pub shape: LiquidityShape,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub bins: Vec<BinDeposit>,
+}
+
+/// Opens a position for `user` over `range` and deposits both amounts spread by `shape`.
+/// Returns a transaction the user only has to sign as fee payer.
+pub async fn build_create_position_transaction(
+    ctx: &AppContext,
+    pair_key: Pubkey,
+    user: Pubkey,
+    range: PositionRange,
+    amount_x: u64,
+    amount_y: u64,
+    shape: LiquidityShape,
+) -> Result<BuiltPosition> {
+    let pair = live_client(ctx, pair_key).await?.pair_summary().await;
+    let program_id = ctx.config.dlmm_program_id;
+
+    let [token_x, token_y] = ctx
+        .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+    let (lower_bin_id, upper_bin_id) = match range {
+        PositionRange::Bins { lower, upper } => (lower, upper),
+        PositionRange::Prices { min, max } => {
+            // Widened to whole bins so that both bounds are covered
+            let bin_id_of = |price: f64, rounding: Rounding| {
+                id_from_ui_price(pair.bin_step, price, &token_x, &token_y, rounding)
+                    .ok_or_else(|| AppError::InvalidInput(format!("Invalid price {}", price)))
+            };
+            (
+                bin_id_of(min, Rounding::Down)?,
+                bin_id_of(max, Rounding::Up)?,
+            )
+        }
+    };
+    ensure!(
+        lower_bin_id <= upper_bin_id,
+        AppError::InvalidInput("The lower bound must not be above the upper bound".to_string())
+    );
+    ensure!(
+        ((upper_bin_id - lower_bin_id) as usize) < POSITION_MAX_BINS,
+        AppError::InvalidInput(format!(
+            "A position spans at most {} bins",
+            POSITION_MAX_BINS
+        ))
+    );
+
+    let distributions = distribute_liquidity(shape, pair.active_id, lower_bin_id, upper_bin_id);
+    ensure_deposit_fits(&distributions, amount_x, amount_y)?;
+
+    let position_mint = Keypair::new();
+    let liquidity_accounts =
+        PositionLiquidityAccounts::load(ctx, &pair, user, position_mint.pubkey(), lower_bin_id)
+            .await?;
+
+    // Bins can only be deposited into once their array is initialized
+    let bin_array_index = bin_array_index_of(lower_bin_id);
+    let bin_arrays = ctx
+        .get_bin_arrays(
+            pair_key,
+            program_id,
+            vec![bin_array_index, bin_array_index + 1],
+        )
+        .await?;
+    let mut instructions: Vec<Instruction> = [bin_array_index, bin_array_index + 1]
+        .into_iter()
+        .filter(|index| !bin_arrays.contains_key(index))
+        .map(|index| initialize_bin_array_instruction(&program_id, &pair_key, &user, index))
+        .collect();
+
+    instructions.push(create_position_instruction(
+        &program_id,
+        &pair,
+        &user,
+        &position_mint.pubkey(),
+        lower_bin_id,
+        upper_bin_id,
+    ));
+    instructions.extend(deposit_instructions(
+        &liquidity_accounts,
+        amount_x,
+        amount_y,
+        &distributions,
+    )?);
+
+    let (mut transaction, last_valid_block_height) =
+        latest_unsigned_transaction(ctx, &user, &instructions).await?;
+
+    // The new mint signs right away, leaving only the user's signature
+    let mint_index = transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .position(|key| *key == position_mint.pubkey())
+        .context("Position mint is missing from the transaction")?;
+    transaction.signatures[mint_index] =
+        position_mint.sign_message(&transaction.message.serialize());
+
+    Ok(BuiltPosition {
+        transaction: STANDARD.encode(bincode::serialize(&transaction)?),
+        last_valid_block_height,
+        position: position_address(&position_mint.pubkey(), &program_id).to_string(),
+        position_mint: position_mint.pubkey().to_string(),
+        lower_bin_id,
+        upper_bin_id,
+        active_id: pair.active_id,
+        shape,
+        amount_x,
+        amount_y,
+        bins: deposit_preview(
+            &pair,
+            (token_x.decimals, token_y.decimals),
+            &distributions,
+            amount_x,
+            amount_y,
+            &[],
+            &bin_arrays,
+        ),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuiltLiquidityChange<B> {
+    /// Base64 encoded unsigned v0 transaction
+    pub transaction: String,
+    pub last_valid_block_height: u64,
+    pub position: String,
+    pub active_id: u32,
+    /// Raw amounts deposited, or expected back from a withdrawal
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub bins: Vec<B>,
+}
+
+/// Loads a position, failing unless `user` holds its NFT in the associated token account
+async fn load_user_position(ctx: &AppContext, address: Pubkey, user: Pubkey) -> Result<Position> {
+    let position =
+        State::generate_position(ctx.accounts.clone(), address, ctx.config.dlmm_program_id).await?;
+
+    let nft_account = ctx
+        .accounts
+        .get_multiple_accounts(&[position_token_account(&user, &position.position_mint)])
+        .await
+        .context("Failed to get position token account")?
+        .into_iter()
+        .next()
+        .flatten();
+    ensure!(
+        nft_account.is_some_and(|account| token_account_amount(&account.data) == Some(1)),
+        AppError::InvalidInput(format!("{} does not hold position {}", user, address))
+    );
+
+    Ok(position)
+}
+
+/// Deposits more liquidity into an existing position, spread over all of its bins by `shape`
+pub async fn build_add_liquidity_transaction(
+    ctx: &AppContext,
+    position_address: Pubkey,
+    user: Pubkey,
+    amount_x: u64,
+    amount_y: u64,
+    shape: LiquidityShape,
+) -> Result<BuiltLiquidityChange<BinDeposit>> {
+    let position = load_user_position(ctx, position_address, user).await?;
+
+    let pair = live_client(ctx, position.pair).await?.pair_summary().await;
+
+    let distributions = distribute_liquidity(
+        shape,
+        pair.active_id,
+        position.lower_bin_id,
+        position.upper_bin_id,
+    );
+    ensure_deposit_fits(&distributions, amount_x, amount_y)?;
+
+    let [token_x, token_y] = ctx
+        .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+    let bin_arrays = ctx
+        .get_bin_arrays(
+            position.pair,
+            ctx.config.dlmm_program_id,
+            position.bin_array_indexes(),
+        )
+        .await?;
+    let holdings = position_bin_amounts(&position, &bin_arrays);
+
+    let liquidity_accounts = PositionLiquidityAccounts::load(
+        ctx,
+        &pair,
+        user,
+        position.position_mint,
+        position.lower_bin_id,
+    )
+    .await?;
+    let instructions =
+        deposit_instructions(&liquidity_accounts, amount_x, amount_y, &distributions)?;
+    let (transaction, last_valid_block_height) =
+        latest_unsigned_transaction(ctx, &user, &instructions).await?;
+
+    Ok(BuiltLiquidityChange {
+        transaction: STANDARD.encode(bincode::serialize(&transaction)?),
+        last_valid_block_height,
+        position: position_address.to_string(),
+        active_id: pair.active_id,
+        amount_x,
+        amount_y,
+        bins: deposit_preview(
+            &pair,
+            (token_x.decimals, token_y.decimals),
+            &distributions,
+            amount_x,
+            amount_y,
+            &holdings,
+            &bin_arrays,
+        ),
+    })
+}
+
+/// Withdraws `bps` of the position's liquidity from `bin_ids`, or from every bin when `None`
+pub async fn build_remove_liquidity_transaction(
+    ctx: &AppContext,
+    position_address: Pubkey,
+    user: Pubkey,
+    bps: u16,
+    bin_ids: Option<Vec<u32>>,
+) -> Result<BuiltLiquidityChange<BinWithdrawal>> {
+    ensure!(
+        bps > 0 && bps <= BPS,
+        AppError::InvalidInput("`bps` must be between 1 and 10000".to_string())
+    );
+
+    let position = load_user_position(ctx, position_address, user).await?;
+    let range = position.lower_bin_id..=position.upper_bin_id;
+    if let Some(bin_id) = bin_ids
+        .iter()
+        .flatten()
+        .find(|bin_id| !range.contains(bin_id))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Bin {} is outside the position's bins {}..={}",
+            bin_id, position.lower_bin_id, position.upper_bin_id
+        ))
+        .into());
+    }
+
+    let pair = live_client(ctx, position.pair).await?.pair_summary().await;
+
+    let [token_x, token_y] = ctx
+        .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+    let bin_arrays = ctx
+        .get_bin_arrays(
+            position.pair,
+            ctx.config.dlmm_program_id,
+            position.bin_array_indexes(),
+        )
+        .await?;
+    let holdings = position_bin_amounts(&position, &bin_arrays);
+
+    let chosen = |bin_id: u32| bin_ids.as_ref().is_none_or(|ids| ids.contains(&bin_id));
+    let shares: Vec<u128> = holdings
+        .iter()
+        .map(|holding| {
+            if chosen(holding.bin_id) {
+                portion_of_share(holding.liquidity_share, bps)
+            } else {
+                0
+            }
+        })
+        .collect();
+    ensure!(
+        shares.iter().any(|share| *share > 0),
+        AppError::InvalidInput("The chosen bins hold no liquidity".to_string())
+    );
+
+    let bins: Vec<BinWithdrawal> = holdings
+        .iter()
+        .zip(&shares)
+        .filter(|(_, share)| **share > 0)
+        .map(|(holding, share)| BinWithdrawal {
+            bin_id: holding.bin_id,
+            price: ui_price_from_id(pair.bin_step, holding.bin_id, &token_x, &token_y),
+            liquidity_share: share.to_string(),
+            amount_x: share_of(holding.amount_x, *share, holding.liquidity_share),
+            amount_y: share_of(holding.amount_y, *share, holding.liquidity_share),
+        })
+        .collect();
+
+    let liquidity_accounts = PositionLiquidityAccounts::load(
+        ctx,
+        &pair,
+        user,
+        position.position_mint,
+        position.lower_bin_id,
+    )
+    .await?;
+    let instructions = withdraw_instructions(&liquidity_accounts, &shares)?;
+    let (transaction, last_valid_block_height) =
+        latest_unsigned_transaction(ctx, &user, &instructions).await?;
+
+    Ok(BuiltLiquidityChange {
+        transaction: STANDARD.encode(bincode::serialize(&transaction)?),
+        last_valid_block_height,
+        position: position_address.to_string(),
+        active_id: pair.active_id,
+        amount_x: bins.iter().map(|bin| bin.amount_x).sum(),
+        amount_y: bins.iter().map(|bin| bin.amount_y).sum(),
+        bins,
+    })
+}
+
+/// `share * bps / 10_000`, everything when `bps` is 10_000 so no dust is left behind
+fn portion_of_share(share: u128, bps: u16) -> u128 {
+    if bps == BPS {
+        return share;
+    }
+
+    match share.checked_mul(bps as u128) {
+        Some(product) => product / BPS as u128,
+        None => share / BPS as u128 * bps as u128,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        app::DLMM_PROGRAM_ID,
+        state::{bin_array_address, fetch_position},
+        test_support::TestBank,
+    };
+
+    const ACTIVE_ID: u32 = 8_388_608;
+    const SHAPES: [LiquidityShape; 3] = [
+        LiquidityShape::Spot,
+        LiquidityShape::Curve,
+        LiquidityShape::BidAsk,
+    ];
+
+    #[test]
+    fn weights_to_bps_sums_to_10000() {
+        for weights in [
+            vec![1.0],
+            vec![1.0; 3],
+            vec![1.0; 7],
+            vec![1.0, 2.0, 3.0],
+            vec![0.1, 0.7, 0.2, 1e-9],
+            vec![0.0, 5.0, 0.0],
+        ] {
+            let bps = weights_to_bps(&weights);
+            assert_eq!(
+                bps.iter().map(|bps| *bps as u32).sum::<u32>(),
+                BPS as u32,
+                "{:?}",
+                weights
+            );
+            for (weight, bps) in weights.iter().zip(&bps) {
+                if *weight == 0.0 {
+                    assert_eq!(*bps, 0, "{:?}", weights);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weights_to_bps_without_weight_is_all_zeros() {
+        assert_eq!(weights_to_bps(&[0.0, 0.0]), vec![0, 0]);
+        assert!(weights_to_bps(&[]).is_empty());
+    }
+
+    #[test]
+    fn distribution_keeps_x_above_and_y_below_the_active_bin() {
+        let ranges = [
+            (ACTIVE_ID - 5, ACTIVE_ID + 5),
+            (ACTIVE_ID, ACTIVE_ID + 3),
+            (ACTIVE_ID - 3, ACTIVE_ID),
+            (ACTIVE_ID, ACTIVE_ID),
+            (ACTIVE_ID + 2, ACTIVE_ID + 9),
+            (ACTIVE_ID - 9, ACTIVE_ID - 2),
+        ];
+
+        for shape in SHAPES {
+            for (lower, upper) in ranges {
+                let distributions = distribute_liquidity(shape, ACTIVE_ID, lower, upper);
+                assert_eq!(distributions.len() as u32, upper - lower + 1);
+
+                let total_x: u32 = distributions.iter().map(|d| d.distribution_x as u32).sum();
+                let total_y: u32 = distributions.iter().map(|d| d.distribution_y as u32).sum();
+                assert_eq!(total_x, if upper >= ACTIVE_ID { 10_000 } else { 0 });
+                assert_eq!(total_y, if lower <= ACTIVE_ID { 10_000 } else { 0 });
+
+                for (distribution, bin_id) in distributions.iter().zip(lower..=upper) {
+                    assert_eq!(
+                        distribution.relative_bin_id,
+                        bin_id as i32 - ACTIVE_ID as i32
+                    );
+                    if bin_id < ACTIVE_ID {
+                        assert_eq!(distribution.distribution_x, 0, "{:?}", shape);
+                    }
+                    if bin_id > ACTIVE_ID {
+                        assert_eq!(distribution.distribution_y, 0, "{:?}", shape);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distribution_follows_the_shape() {
+        let x_side = |shape| -> Vec<u16> {
+            distribute_liquidity(shape, ACTIVE_ID, ACTIVE_ID, ACTIVE_ID + 4)
+                .iter()
+                .map(|d| d.distribution_x)
+                .collect()
+        };
+
+        let spot = x_side(LiquidityShape::Spot);
+        assert!(spot.iter().max().unwrap() - spot.iter().min().unwrap() <= spot.len() as u16);
+        assert!(x_side(LiquidityShape::Curve)
+            .windows(2)
+            .all(|w| w[0] >= w[1]));
+        assert!(x_side(LiquidityShape::BidAsk)
+            .windows(2)
+            .all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn portion_of_share_takes_everything_at_10000_bps() {
+        assert_eq!(portion_of_share(u128::MAX, BPS), u128::MAX);
+        assert_eq!(portion_of_share(1_000, 2_500), 250);
+        assert_eq!(
+            portion_of_share(u128::MAX, 5_000),
+            u128::MAX / BPS as u128 * 5_000
+        );
+    }
+
+    fn sol_pair_accounts(wrapped_sol_exists: bool) -> PositionLiquidityAccounts {
+        PositionLiquidityAccounts {
+            program_id: DLMM_PROGRAM_ID,
+            pair_key: Pubkey::new_unique(),
+            token_mint_x: native_mint::ID,
+            token_mint_y: Pubkey::new_unique(),
+            token_program_x: spl_token::ID,
+            token_program_y: spl_token::ID,
+            user: Pubkey::new_unique(),
+            position_mint: Pubkey::new_unique(),
+            lower_bin_id: ACTIVE_ID,
+            wrapped_sol_exists,
+        }
+    }
+
+    #[test]
+    fn sol_is_unwrapped_only_from_a_wrapped_sol_account_the_transaction_creates() -> Result<()> {
+        for wrapped_sol_exists in [false, true] {
+            let accounts = sol_pair_accounts(wrapped_sol_exists);
+            let unwrap = unwrap_sol_instruction(&accounts.user)?;
+            let distributions = distribute_liquidity(
+                LiquidityShape::Spot,
+                ACTIVE_ID,
+                ACTIVE_ID - 1,
+                ACTIVE_ID + 1,
+            );
+
+            let deposit = deposit_instructions(&accounts, 1_000, 1_000, &distributions)?;
+            let withdraw = withdraw_instructions(&accounts, &[1, 2, 3])?;
+            for instructions in [deposit, withdraw] {
+                assert_eq!(
+                    instructions.contains(&unwrap),
+                    !wrapped_sol_exists,
+                    "wrapped SOL account exists: {}",
+                    wrapped_sol_exists
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decrease_position_encodes_one_share_per_bin() {
+        let accounts = sol_pair_accounts(false);
+        let shares = [0, 1, u128::MAX];
+        let instruction = decrease_position_instruction(&accounts, &shares);
+
+        let (discriminator, data) = instruction.data.split_at(8);
+        assert_eq!(
+            discriminator,
+            anchor_discriminator("global:decrease_position")
+        );
+        let (len, data) = data.split_at(4);
+        assert_eq!(len, 3u32.to_le_bytes());
+        let decoded: Vec<u128> = data
+            .chunks_exact(16)
+            .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(decoded, shares);
+        assert_eq!(instruction.accounts, accounts.account_metas());
+    }
+
+    /// Checks what left the user's token account, wrapped SOL moves through lamports that also
+    /// pay the fees so it is left out
+    async fn assert_spent(
+        bank: &mut TestBank,
+        mint: &Pubkey,
+        before: u64,
+        previewed: u64,
+        amount: u64,
+    ) -> Result<()> {
+        if *mint == native_mint::ID {
+            return Ok(());
+        }
+        let spent = before - bank.token_balance(mint).await?;
+        assert!(
+            previewed <= spent && spent <= amount,
+            "spent {} of {}, previewed {}",
+            spent,
+            mint,
+            previewed
+        );
+        Ok(())
+    }
+
+    async fn create_position(
+        bank: &mut TestBank,
+        lower: u32,
+        upper: u32,
+        amount_x: u64,
+        amount_y: u64,
+    ) -> Result<BuiltPosition> {
+        let ctx = bank.app_context().await?;
+        let built = build_create_position_transaction(
+            &ctx,
+            bank.pair.address,
+            bank.user.pubkey(),
+            PositionRange::Bins { lower, upper },
+            amount_x,
+            amount_y,
+            LiquidityShape::Spot,
+        )
+        .await?;
+        bank.sign_and_process(&built.transaction).await?;
+        Ok(built)
+    }
+
+    async fn load_position(bank: &mut TestBank, address: &str) -> Result<Position> {
+        let ctx = bank.app_context().await?;
+        fetch_position(
+            ctx.accounts.clone(),
+            Pubkey::from_str(address)?,
+            ctx.config.dlmm_program_id,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn opens_a_position_in_new_bin_arrays() -> Result<()> {
+        let Some(mut bank) = TestBank::start(1_000_000_000).await? else {
+            return Ok(());
+        };
+        let mint_x = bank.pair.token_mint_x;
+        let before_x = bank.token_balance(&mint_x).await?;
+
+        // Far enough above the active bin for its bin arrays to need initializing
+        let lower = (bin_array_index_of(bank.pair.active_id) + 3) * BIN_ARRAY_SIZE + 10;
+        let built = create_position(&mut bank, lower, lower + 4, 1_000_000, 0).await?;
+
+        for index in [bin_array_index_of(lower), bin_array_index_of(lower) + 1] {
+            let bin_array = bin_array_address(&bank.pair.address, &DLMM_PROGRAM_ID, index);
+            assert!(
+                bank.context
+                    .banks_client
+                    .get_account(bin_array)
+                    .await?
+                    .is_some(),
+                "bin array {} was not initialized",
+                index
+            );
+        }
+
+        let position = load_position(&mut bank, &built.position).await?;
+        assert_eq!(
+            (position.lower_bin_id, position.upper_bin_id),
+            (lower, lower + 4)
+        );
+        assert!(position.bin_shares().all(|(_, share)| share > 0));
+
+        let previewed: u64 = built.bins.iter().map(|bin| bin.amount_x).sum();
+        assert_spent(&mut bank, &mint_x, before_x, previewed, 1_000_000).await
+    }
+
+    #[tokio::test]
+    async fn adds_liquidity_to_every_bin_of_a_position() -> Result<()> {
+        let Some(mut bank) = TestBank::start(1_000_000_000).await? else {
+            return Ok(());
+        };
+        let active_id = bank.pair.active_id;
+        let built = create_position(
+            &mut bank,
+            active_id - 3,
+            active_id + 3,
+            1_000_000,
+            1_000_000,
+        )
+        .await?;
+        let created = load_position(&mut bank, &built.position).await?;
+
+        let (mint_x, mint_y) = (bank.pair.token_mint_x, bank.pair.token_mint_y);
+        let (before_x, before_y) = (
+            bank.token_balance(&mint_x).await?,
+            bank.token_balance(&mint_y).await?,
+        );
+
+        let ctx = bank.app_context().await?;
+        let added = build_add_liquidity_transaction(
+            &ctx,
+            created.address,
+            bank.user.pubkey(),
+            500_000,
+            500_000,
+            LiquidityShape::Curve,
+        )
+        .await?;
+        bank.sign_and_process(&added.transaction).await?;
+
+        let position = load_position(&mut bank, &built.position).await?;
+        for ((bin_id, before), (_, after)) in created.bin_shares().zip(position.bin_shares()) {
+            let deposited = added
+                .bins
+                .iter()
+                .any(|bin| bin.bin_id == bin_id && (bin.amount_x > 0 || bin.amount_y > 0));
+            assert_eq!(after > before, deposited, "bin {}", bin_id);
+        }
+
+        let previewed_x: u64 = added.bins.iter().map(|bin| bin.amount_x).sum();
+        let previewed_y: u64 = added.bins.iter().map(|bin| bin.amount_y).sum();
+        assert_spent(&mut bank, &mint_x, before_x, previewed_x, 500_000).await?;
+        assert_spent(&mut bank, &mint_y, before_y, previewed_y, 500_000).await
+    }
+
+    #[tokio::test]
+    async fn removes_a_portion_of_the_chosen_bins_only() -> Result<()> {
+        let Some(mut bank) = TestBank::start(1_000_000_000).await? else {
+            return Ok(());
+        };
+        let active_id = bank.pair.active_id;
+        let built = create_position(
+            &mut bank,
+            active_id - 3,
+            active_id + 3,
+            1_000_000,
+            1_000_000,
+        )
+        .await?;
+        let created = load_position(&mut bank, &built.position).await?;
+
+        let (mint_x, mint_y) = (bank.pair.token_mint_x, bank.pair.token_mint_y);
+        let (before_x, before_y) = (
+            bank.token_balance(&mint_x).await?,
+            bank.token_balance(&mint_y).await?,
+        );
+
+        // One bin on each side of the active bin, so both tokens come back
+        let chosen = [active_id - 2, active_id + 2];
+        let ctx = bank.app_context().await?;
+        let removed = build_remove_liquidity_transaction(
+            &ctx,
+            created.address,
+            bank.user.pubkey(),
+            5_000,
+            Some(chosen.to_vec()),
+        )
+        .await?;
+        bank.sign_and_process(&removed.transaction).await?;
+
+        let position = load_position(&mut bank, &built.position).await?;
+        for ((bin_id, before), (_, after)) in created.bin_shares().zip(position.bin_shares()) {
+            let expected = if chosen.contains(&bin_id) {
+                before - portion_of_share(before, 5_000)
+            } else {
+                before
+            };
+            assert_eq!(after, expected, "bin {}", bin_id);
+        }
+
+        for (mint, before, previewed) in [
+            (mint_x, before_x, removed.amount_x),
+            (mint_y, before_y, removed.amount_y),
+        ] {
+            if mint != native_mint::ID {
+                assert_eq!(bank.token_balance(&mint).await? - before, previewed);
+            }
+        }
+
+        let ctx = bank.app_context().await?;
+        let emptied = build_remove_liquidity_transaction(
+            &ctx,
+            created.address,
+            bank.user.pubkey(),
+            BPS,
+            None,
+        )
+        .await?;
+        bank.sign_and_process(&emptied.transaction).await?;
+
+        let position = load_position(&mut bank, &built.position).await?;
+        assert!(position.bin_shares().all(|(_, share)| share == 0));
+        Ok(())
+    }
+}
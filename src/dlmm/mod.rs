@@ -1,3 +1,20 @@
+mod account_subscriptions;
+mod client_registry;
 mod dlmm_client;
+pub mod fees;
+mod liquidity;
+mod quote;
+mod rewards;
+mod simulation;
+mod swap;
+mod swap_path;
 
+pub use account_subscriptions::*;
+pub use client_registry::*;
 pub use dlmm_client::*;
+pub use liquidity::*;
+pub use quote::*;
+pub use rewards::*;
+pub use simulation::*;
+pub use swap::*;
+pub use swap_path::*;
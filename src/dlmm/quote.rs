@@ -0,0 +1,172 @@
+use anyhow::{bail, Result};
+use std::sync::atomic::Ordering;
+
+use jupiter_amm_interface::{Amm, QuoteParams};
+use saros_dlmm::SarosDlmm;
+use saros_sdk::utils::helper::is_swap_for_y;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    app::AppContext,
+    dlmm::replay_swap,
+    error::AppError,
+    pricing::{to_ui_price, ui_price_from_id},
+    state::{QuoteResponse, QuoteSwapMode},
+    utils::to_ui_amount,
+};
+
+pub struct QuoteInput {
+    pub source_mint: Pubkey,
+    pub destination_mint: Pubkey,
+    /// Input amount for `ExactIn`, output amount for `ExactOut`
+    pub amount: u64,
+    pub swap_mode: QuoteSwapMode,
+    pub slippage_bps: u16,
+    /// Replay the swap across the bins and attach the path to the quote
+    pub verbose: bool,
+}
+
+/// Quotes a swap and prices it against the active bin
+pub async fn quote_pair(
+    ctx: &AppContext,
+    saros_dlmm: &SarosDlmm,
+    input: &QuoteInput,
+) -> Result<QuoteResponse> {
+    if input.slippage_bps > 10_000 {
+        bail!(AppError::InvalidInput(
+            "Slippage cannot exceed 10000 bps".to_string()
+        ));
+    }
+
+    let pair = &saros_dlmm.pair;
+    let swap_for_y = is_swap_for_y(input.source_mint, pair.token_mint_x);
+    let (expected_source, expected_destination) = if swap_for_y {
+        (pair.token_mint_x, pair.token_mint_y)
+    } else {
+        (pair.token_mint_y, pair.token_mint_x)
+    };
+    if input.source_mint != expected_source || input.destination_mint != expected_destination {
+        bail!(AppError::InvalidInput(
+            "Source and destination mints do not match the pair".to_string()
+        ));
+    }
+
+    // The AMM only holds the bin arrays around the active bin, it fails to quote an amount
+    // they cannot fill
+    let quote = saros_dlmm
+        .quote(&QuoteParams {
+            amount: input.amount,
+            input_mint: input.source_mint,
+            output_mint: input.destination_mint,
+            swap_mode: input.swap_mode.into(),
+        })
+        .map_err(|e| AppError::InsufficientLiquidity(e.to_string()))?;
+
+    let [meta_x, meta_y] = ctx
+        .fetch_pair_token_info(pair.token_mint_x, pair.token_mint_y)
+        .await?;
+    let (source_meta, destination_meta) = if swap_for_y {
+        (&meta_x, &meta_y)
+    } else {
+        (&meta_y, &meta_x)
+    };
+    let fee_decimals = if quote.fee_mint == destination_meta.mint {
+        destination_meta.decimals
+    } else {
+        source_meta.decimals
+    };
+
+    let slippage = input.slippage_bps as u128;
+    let (min_out_amount, max_in_amount) = match input.swap_mode {
+        QuoteSwapMode::ExactIn => (
+            (quote.out_amount as u128 * (10_000 - slippage) / 10_000) as u64,
+            quote.in_amount,
+        ),
+        QuoteSwapMode::ExactOut => (
+            quote.out_amount,
+            (quote.in_amount as u128 * (10_000 + slippage)).div_ceil(10_000) as u64,
+        ),
+    };
+
+    // Prices are quoted as destination tokens per source token, in UI units
+    let price_x_in_y = ui_price_from_id(pair.bin_step, pair.active_id, &meta_x, &meta_y);
+    let spot_price = if swap_for_y {
+        price_x_in_y
+    } else {
+        1.0 / price_x_in_y
+    };
+
+    let in_amount_ui = to_ui_amount(quote.in_amount, source_meta.decimals);
+    let out_amount_ui = to_ui_amount(quote.out_amount, destination_meta.decimals);
+    let execution_price = if in_amount_ui > 0.0 {
+        out_amount_ui / in_amount_ui
+    } else {
+        0.0
+    };
+
+    // Price impact only measures the move along the bins, so fees taken from the input are excluded
+    let fee_in_input = if quote.fee_mint == input.source_mint {
+        quote.fee_amount
+    } else {
+        0
+    };
+    let swapped_in_ui = to_ui_amount(
+        quote.in_amount.saturating_sub(fee_in_input),
+        source_meta.decimals,
+    );
+    let price_impact_bps = if swapped_in_ui > 0.0 && spot_price > 0.0 {
+        ((spot_price - out_amount_ui / swapped_in_ui) / spot_price * 10_000.0).max(0.0)
+    } else {
+        0.0
+    };
+
+    let path = if input.verbose {
+        // Same two bin arrays around the active bin that `PoolState` holds
+        let bin_array_index = pair.bin_array_index();
+        let bin_arrays = ctx
+            .get_bin_arrays(
+                saros_dlmm.key(),
+                saros_dlmm.program_id(),
+                vec![bin_array_index, bin_array_index + 1],
+            )
+            .await?;
+        // The clock the quote itself was computed against, shared by every pair
+        let now = ctx.clock_ref.unix_timestamp.load(Ordering::Relaxed).max(0) as u64;
+
+        let mut path = replay_swap(
+            pair,
+            &bin_arrays,
+            swap_for_y,
+            input.swap_mode == QuoteSwapMode::ExactIn,
+            input.amount,
+            now,
+        );
+        for step in path.steps.iter_mut() {
+            step.price = to_ui_price(step.price, meta_x.decimals, meta_y.decimals);
+        }
+        Some(path)
+    } else {
+        None
+    };
+
+    Ok(QuoteResponse {
+        source_mint: input.source_mint.to_string(),
+        destination_mint: input.destination_mint.to_string(),
+        swap_mode: input.swap_mode,
+        fixed_side: input.swap_mode.fixed_side().to_string(),
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+        fee_amount: quote.fee_amount,
+        fee_mint: quote.fee_mint.to_string(),
+        in_amount_ui,
+        out_amount_ui,
+        fee_amount_ui: to_ui_amount(quote.fee_amount, fee_decimals),
+        slippage_bps: input.slippage_bps,
+        min_out_amount,
+        max_in_amount,
+        spot_price,
+        execution_price,
+        price_impact_bps,
+        path,
+    })
+}
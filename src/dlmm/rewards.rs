@@ -0,0 +1,492 @@
+use anyhow::{ensure, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account_client::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    app::AppContext,
+    dlmm::{position_bin_arrays, position_token_account, unsigned_transaction, SwapSimulator},
+    error::AppError,
+    state::{bin_array_index_of, Position, RewarderHook, State},
+    utils::{anchor_discriminator, to_ui_amount},
+};
+
+/// Pays everything the position earned from the hook into the user's reward token account.
+/// The hook settles the position against the DLMM bin arrays holding its shares first.
+pub fn claim_reward_instruction(
+    dlmm_program_id: &Pubkey,
+    hook: &RewarderHook,
+    position: &Position,
+    user: &Pubkey,
+    reward_token_program: &Pubkey,
+) -> Instruction {
+    let (bin_array_lower, bin_array_upper) =
+        position_bin_arrays(&position.pair, dlmm_program_id, position.lower_bin_id);
+    let index = bin_array_index_of(position.lower_bin_id);
+
+    Instruction {
+        program_id: hook.program_id,
+        accounts: vec![
+            AccountMeta::new(hook.address, false),
+            AccountMeta::new_readonly(position.pair, false),
+            AccountMeta::new_readonly(position.address, false),
+            AccountMeta::new_readonly(position_token_account(user, &position.position_mint), false),
+            AccountMeta::new(hook.position_address(&position.address), false),
+            AccountMeta::new_readonly(bin_array_lower, false),
+            AccountMeta::new_readonly(bin_array_upper, false),
+            AccountMeta::new(hook.bin_array_address(index), false),
+            AccountMeta::new(hook.bin_array_address(index + 1), false),
+            AccountMeta::new_readonly(hook.reward_token_mint, false),
+            AccountMeta::new(hook.reward_reserve, false),
+            AccountMeta::new(
+                get_associated_token_address_with_program_id(
+                    user,
+                    &hook.reward_token_mint,
+                    reward_token_program,
+                ),
+                false,
+            ),
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(*reward_token_program, false),
+        ],
+        data: anchor_discriminator("global:claim").to_vec(),
+    }
+}
+
+fn transaction_size(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    blockhash: Hash,
+) -> Result<usize> {
+    let transaction = unsigned_transaction(payer, instructions, blockhash)?;
+    Ok(bincode::serialized_size(&transaction)? as usize)
+}
+
+/// Instructions that go together in one transaction. `setup` instructions, like creating a token
+/// account several groups pay into, are shared: each runs once, at the head of the first
+/// transaction holding a group that needs it.
+#[derive(Clone, Debug, Default)]
+pub struct InstructionGroup {
+    pub setup: Vec<Instruction>,
+    pub instructions: Vec<Instruction>,
+}
+
+/// A transaction being packed, its setup instructions run first
+#[derive(Default)]
+struct PackedTransaction {
+    setup: Vec<Instruction>,
+    instructions: Vec<Instruction>,
+    groups: usize,
+}
+
+impl PackedTransaction {
+    fn all(&self) -> Vec<Instruction> {
+        self.setup
+            .iter()
+            .chain(&self.instructions)
+            .cloned()
+            .collect()
+    }
+
+    /// The transaction with `group` added, without the setup instructions `done` already runs
+    fn with(&self, group: &InstructionGroup, done: &[Instruction]) -> Self {
+        let mut setup = self.setup.clone();
+        for instruction in &group.setup {
+            if !done.contains(instruction) && !setup.contains(instruction) {
+                setup.push(instruction.clone());
+            }
+        }
+        let mut instructions = self.instructions.clone();
+        instructions.extend(group.instructions.iter().cloned());
+
+        PackedTransaction {
+            setup,
+            instructions,
+            groups: self.groups + 1,
+        }
+    }
+}
+
+/// Packs groups of instructions into as few transactions as fit in a packet, in order. A group
+/// never spans two transactions, and a setup instruction only runs in the first transaction
+/// needing it, so the transactions land in order. Returns each transaction with the number of
+/// groups it holds.
+pub fn pack_transactions(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+    blockhash: Hash,
+) -> Result<Vec<(VersionedTransaction, usize)>> {
+    let mut packed: Vec<PackedTransaction> = vec![];
+    let mut done: Vec<Instruction> = vec![];
+    let mut current = PackedTransaction::default();
+
+    for group in groups {
+        let next = current.with(group, &done);
+        if transaction_size(payer, &next.all(), blockhash)? <= PACKET_DATA_SIZE {
+            current = next;
+            continue;
+        }
+
+        done.extend(current.setup.iter().cloned());
+        let next = PackedTransaction::default().with(group, &done);
+        ensure!(
+            transaction_size(payer, &next.all(), blockhash)? <= PACKET_DATA_SIZE,
+            "A group of {} instructions does not fit in a transaction",
+            group.setup.len() + group.instructions.len()
+        );
+        packed.push(std::mem::replace(&mut current, next));
+    }
+    if current.groups > 0 {
+        packed.push(current);
+    }
+
+    packed
+        .into_iter()
+        .map(|transaction| {
+            Ok((
+                unsigned_transaction(payer, &transaction.all(), blockhash)?,
+                transaction.groups,
+            ))
+        })
+        .collect()
+}
+
+/// Rewards claimed from one position
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardClaim {
+    pub position: String,
+    pub pair: String,
+    pub reward_mint: String,
+    pub symbol: String,
+    pub raw_amount: u64,
+    /// The same amount in UI units
+    pub amount: f64,
+    /// Index of the transaction claiming it
+    pub transaction: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuiltClaims {
+    /// Base64 encoded unsigned v0 transactions, to land in order: a reward token account is
+    /// only created by the first transaction paying into it
+    pub transactions: Vec<String>,
+    pub last_valid_block_height: u64,
+    pub claims: Vec<RewardClaim>,
+}
+
+/// Runs a claim on its own and checks it pays at least what was computed as pending. The hook
+/// accounts are decoded by hand, a claim its program rejects or underpays is not handed out.
+async fn simulate_claim(
+    simulator: &impl SwapSimulator,
+    user: &Pubkey,
+    hook: &RewarderHook,
+    group: &InstructionGroup,
+    reward_token_account: Pubkey,
+    raw_amount: u64,
+    blockhash: Hash,
+) -> Result<()> {
+    let instructions: Vec<Instruction> = group
+        .setup
+        .iter()
+        .chain(&group.instructions)
+        .cloned()
+        .collect();
+    let transaction = unsigned_transaction(user, &instructions, blockhash)?;
+
+    let before = simulator.token_balances(&[reward_token_account]).await?;
+    let outcome = simulator.run(transaction, &[reward_token_account]).await?;
+    if let Some(error) = outcome.error {
+        return Err(AppError::DecodeFailure(format!(
+            "rewarder hook {}: claiming fails in simulation, {}",
+            hook.address, error
+        ))
+        .into());
+    }
+
+    let received = outcome.post_balances[0].saturating_sub(before[0]);
+    ensure!(
+        received >= raw_amount,
+        AppError::DecodeFailure(format!(
+            "rewarder hook {}: claiming pays {} where {} is pending",
+            hook.address, received, raw_amount
+        ))
+    );
+    Ok(())
+}
+
+/// Claims the pending rewards of every position `user` holds on pairs with a rewarder hook,
+/// batched into as few transactions as possible. The first claim of each hook is simulated
+/// before any transaction is returned.
+pub async fn build_claim_rewards_transactions(
+    ctx: &AppContext,
+    simulator: &impl SwapSimulator,
+    user: Pubkey,
+) -> Result<BuiltClaims> {
+    let positions =
+        State::generate_owner_positions(ctx.accounts.clone(), user, ctx.config.dlmm_program_id)
+            .await?;
+    let mut pairs: Vec<Pubkey> = positions.iter().map(|position| position.pair).collect();
+    pairs.sort();
+    pairs.dedup();
+    let hooks =
+        State::generate_pair_hooks(ctx.accounts.clone(), &pairs, ctx.config.dlmm_program_id)
+            .await?;
+
+    let mut claims = vec![];
+    let mut groups = vec![];
+    // The group and reward token account of the first claim of each hook
+    let mut first_claims: Vec<(&RewarderHook, usize, Pubkey)> = vec![];
+    for position in &positions {
+        let Some(hook) = hooks.get(&position.pair) else {
+            continue;
+        };
+        let raw_amount =
+            State::generate_pending_rewards(ctx.accounts.clone(), hook, position).await?;
+        if raw_amount == 0 {
+            continue;
+        }
+
+        let (mint_account, token) = tokio::try_join!(
+            ctx.get_mint_account(hook.reward_token_mint),
+            ctx.get_token_meta(hook.reward_token_mint)
+        )?;
+        let reward_token_program = mint_account.account.owner;
+        if !first_claims
+            .iter()
+            .any(|(first, _, _)| first.address == hook.address)
+        {
+            let reward_token_account = get_associated_token_address_with_program_id(
+                &user,
+                &hook.reward_token_mint,
+                &reward_token_program,
+            );
+            first_claims.push((hook, groups.len(), reward_token_account));
+        }
+        groups.push(InstructionGroup {
+            setup: vec![create_associated_token_account_idempotent(
+                &user,
+                &user,
+                &hook.reward_token_mint,
+                &reward_token_program,
+            )],
+            instructions: vec![claim_reward_instruction(
+                &ctx.config.dlmm_program_id,
+                hook,
+                position,
+                &user,
+                &reward_token_program,
+            )],
+        });
+        claims.push(RewardClaim {
+            position: position.address.to_string(),
+            pair: position.pair.to_string(),
+            reward_mint: token.mint.to_string(),
+            symbol: token.symbol.clone(),
+            raw_amount,
+            amount: to_ui_amount(raw_amount, token.decimals),
+            transaction: 0,
+        });
+    }
+    ensure!(
+        !groups.is_empty(),
+        AppError::InvalidInput(format!("No rewards to claim for {}", user))
+    );
+
+    let (blockhash, last_valid_block_height) = ctx
+        .rpc_client
+        .get_latest_blockhash_with_commitment(ctx.rpc_client.commitment())
+        .await?;
+    for (hook, index, reward_token_account) in &first_claims {
+        simulate_claim(
+            simulator,
+            &user,
+            hook,
+            &groups[*index],
+            *reward_token_account,
+            claims[*index].raw_amount,
+            blockhash,
+        )
+        .await?;
+    }
+    let packed = pack_transactions(&user, &groups, blockhash)?;
+
+    let mut claim_transactions = packed
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, groups))| std::iter::repeat_n(index, *groups));
+    for claim in &mut claims {
+        claim.transaction = claim_transactions.next().unwrap_or_default();
+    }
+
+    Ok(BuiltClaims {
+        transactions: packed
+            .iter()
+            .map(|(transaction, _)| Ok(STANDARD.encode(bincode::serialize(transaction)?)))
+            .collect::<Result<Vec<_>>>()?,
+        last_valid_block_height,
+        claims,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A claim-sized group: a shared token account creation and an instruction with its own
+    /// accounts
+    fn claim_group(payer: &Pubkey, mint: &Pubkey) -> InstructionGroup {
+        InstructionGroup {
+            setup: vec![create_associated_token_account_idempotent(
+                payer,
+                payer,
+                mint,
+                &spl_token::ID,
+            )],
+            instructions: vec![Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: (0..8)
+                    .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                    .collect(),
+                data: vec![0; 8],
+            }],
+        }
+    }
+
+    /// The instructions of each packed transaction, as (program, accounts)
+    fn unpacked(packed: &[(VersionedTransaction, usize)]) -> Vec<Vec<(Pubkey, Vec<Pubkey>)>> {
+        packed
+            .iter()
+            .map(|(transaction, _)| {
+                let keys = transaction.message.static_account_keys();
+                transaction
+                    .message
+                    .instructions()
+                    .iter()
+                    .map(|instruction| {
+                        (
+                            keys[instruction.program_id_index as usize],
+                            instruction
+                                .accounts
+                                .iter()
+                                .map(|index| keys[*index as usize])
+                                .collect(),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packs_every_group_within_the_packet_size() {
+        let payer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let groups: Vec<InstructionGroup> = (0..12).map(|_| claim_group(&payer, &mint)).collect();
+
+        let packed = pack_transactions(&payer, &groups, Hash::default()).unwrap();
+        assert!(packed.len() > 1, "12 claims cannot fit in one transaction");
+        assert_eq!(
+            packed.iter().map(|(_, groups)| groups).sum::<usize>(),
+            groups.len()
+        );
+        for (transaction, _) in &packed {
+            assert!(bincode::serialized_size(transaction).unwrap() as usize <= PACKET_DATA_SIZE);
+        }
+
+        // The shared token account is created once, by the first transaction and ahead of its
+        // claims
+        let ata_program = spl_associated_token_account_client::program::ID;
+        let transactions = unpacked(&packed);
+        assert_eq!(transactions[0][0].0, ata_program);
+        let claimed: Vec<Pubkey> = transactions
+            .iter()
+            .flatten()
+            .map(|(program, _)| *program)
+            .filter(|program| *program != ata_program)
+            .collect();
+        assert_eq!(
+            transactions
+                .iter()
+                .flatten()
+                .filter(|(program, _)| *program == ata_program)
+                .count(),
+            1
+        );
+
+        // Every claim is there once, in order
+        let expected: Vec<Pubkey> = groups
+            .iter()
+            .map(|group| group.instructions[0].program_id)
+            .collect();
+        assert_eq!(claimed, expected);
+    }
+
+    #[test]
+    fn a_token_account_is_created_at_the_head_of_the_first_transaction_paying_into_it() {
+        let payer = Pubkey::new_unique();
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // The claims of the second mint start in a transaction already holding claims
+        let groups: Vec<InstructionGroup> = (0..12)
+            .map(|i| claim_group(&payer, if i < 5 { &mint_a } else { &mint_b }))
+            .collect();
+
+        let packed = pack_transactions(&payer, &groups, Hash::default()).unwrap();
+        let transactions = unpacked(&packed);
+        let ata_program = spl_associated_token_account_client::program::ID;
+        for (mint, first_claim) in [(mint_a, 0), (mint_b, 5)] {
+            let creations: Vec<(usize, usize)> = transactions
+                .iter()
+                .enumerate()
+                .flat_map(|(t, instructions)| {
+                    instructions
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, (program, accounts))| {
+                            *program == ata_program && accounts.contains(&mint)
+                        })
+                        .map(move |(i, _)| (t, i))
+                })
+                .collect();
+            assert_eq!(creations.len(), 1, "{}", mint);
+
+            // In the transaction of the mint's first claim, before every claim
+            let claim_program = groups[first_claim].instructions[0].program_id;
+            let (t, i) = creations[0];
+            let claim_index = transactions[t]
+                .iter()
+                .position(|(program, _)| *program == claim_program)
+                .expect("the first claim is in the transaction creating its account");
+            assert!(i < claim_index);
+            assert!(transactions[t][..i]
+                .iter()
+                .all(|(program, _)| *program == ata_program));
+        }
+    }
+
+    #[test]
+    fn a_group_larger_than_a_packet_fails() {
+        let payer = Pubkey::new_unique();
+        let group = InstructionGroup {
+            setup: vec![],
+            instructions: vec![Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: (0..40)
+                    .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                    .collect(),
+                data: vec![],
+            }],
+        };
+
+        assert!(pack_transactions(&payer, &[group], Hash::default()).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use anyhow::{bail, Result};
+use std::sync::Arc;
+
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode};
+use saros_sdk::utils::helper::is_swap_for_y;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+};
+#[cfg(test)]
+use solana_program_test::BanksClient;
+#[cfg(test)]
+use solana_sdk::signature::Keypair;
+use solana_sdk::{account::Account, hash::Hash, pubkey::Pubkey, transaction::VersionedTransaction};
+
+use crate::{
+    app::AppContext,
+    dlmm::{
+        build_swap_instruction, live_client, token_account_amount, unsigned_transaction,
+        SwapInstructionParams,
+    },
+    error::AppError,
+};
+
+/// What a backend reports after running a transaction
+pub struct SimulationOutcome {
+    /// Amounts held by the watched token accounts after the transaction, in the order given
+    pub post_balances: Vec<u64>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// A place transactions, swaps or reward claims, can be run without landing them on chain
+pub trait SwapSimulator {
+    async fn latest_blockhash(&self) -> Result<Hash>;
+
+    /// Amounts held by the token accounts, missing accounts hold nothing
+    async fn token_balances(&self, token_accounts: &[Pubkey]) -> Result<Vec<u64>>;
+
+    async fn run(
+        &self,
+        transaction: VersionedTransaction,
+        watched_accounts: &[Pubkey],
+    ) -> Result<SimulationOutcome>;
+}
+
+/// Simulates through `simulateTransaction` on the configured RPC node
+pub struct RpcSwapSimulator {
+    pub rpc_client: Arc<RpcClient>,
+}
+
+impl SwapSimulator for RpcSwapSimulator {
+    async fn latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.rpc_client.get_latest_blockhash().await?)
+    }
+
+    async fn token_balances(&self, token_accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts(token_accounts)
+            .await?;
+
+        Ok(accounts
+            .iter()
+            .map(|account| {
+                account
+                    .as_ref()
+                    .and_then(|account| token_account_amount(&account.data))
+                    .unwrap_or_default()
+            })
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        transaction: VersionedTransaction,
+        watched_accounts: &[Pubkey],
+    ) -> Result<SimulationOutcome> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.rpc_client.commitment()),
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: watched_accounts.iter().map(|key| key.to_string()).collect(),
+            }),
+            ..Default::default()
+        };
+
+        let result = self
+            .rpc_client
+            .simulate_transaction_with_config(&transaction, config)
+            .await?
+            .value;
+
+        let post_accounts = result.accounts.unwrap_or_default();
+        let post_balances = (0..watched_accounts.len())
+            .map(|i| {
+                post_accounts
+                    .get(i)
+                    .and_then(|account| account.as_ref())
+                    .and_then(|account| account.decode::<Account>())
+                    .and_then(|account| token_account_amount(&account.data))
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        Ok(SimulationOutcome {
+            post_balances,
+            units_consumed: result.units_consumed,
+            logs: result.logs.unwrap_or_default(),
+            error: result.err.map(|e| e.to_string()),
+        })
+    }
+}
+
+/// Runs swaps against a `solana-program-test` bank loaded with fixture accounts.
+/// The bank has no `simulateTransaction` with account snapshots, so the transaction is
+/// signed by `payer` (the swapping wallet) and processed for real on the test bank.
+#[cfg(test)]
+pub struct BanksSwapSimulator {
+    pub banks_client: BanksClient,
+    pub payer: Keypair,
+}
+
+#[cfg(test)]
+impl SwapSimulator for BanksSwapSimulator {
+    async fn latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.banks_client.clone().get_latest_blockhash().await?)
+    }
+
+    async fn token_balances(&self, token_accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        let mut banks_client = self.banks_client.clone();
+        let mut balances = Vec::with_capacity(token_accounts.len());
+        for key in token_accounts {
+            let account = banks_client.get_account(*key).await?;
+            balances.push(
+                account
+                    .and_then(|account| token_account_amount(&account.data))
+                    .unwrap_or_default(),
+            );
+        }
+        Ok(balances)
+    }
+
+    async fn run(
+        &self,
+        transaction: VersionedTransaction,
+        watched_accounts: &[Pubkey],
+    ) -> Result<SimulationOutcome> {
+        let transaction = VersionedTransaction::try_new(transaction.message, &[&self.payer])?;
+        let result = self
+            .banks_client
+            .clone()
+            .process_transaction_with_metadata(transaction)
+            .await?;
+
+        let metadata = result.metadata;
+        Ok(SimulationOutcome {
+            post_balances: self.token_balances(watched_accounts).await?,
+            units_consumed: metadata.as_ref().map(|m| m.compute_units_consumed),
+            logs: metadata.map(|m| m.log_messages).unwrap_or_default(),
+            error: result.result.err().map(|e| e.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenBalanceChange {
+    pub mint: String,
+    pub token_account: String,
+    pub pre_balance: u64,
+    pub post_balance: u64,
+    pub change: i128,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapSimulation {
+    pub pair_address: String,
+    pub user: String,
+    pub source_mint: String,
+    pub destination_mint: String,
+    pub amount_in: u64,
+    pub quoted_out_amount: u64,
+    pub balance_changes: Vec<TokenBalanceChange>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Builds the real swap for `user` and runs it on `simulator`
+pub async fn simulate_swap(
+    ctx: &AppContext,
+    simulator: &impl SwapSimulator,
+    pair_key: Pubkey,
+    user: Pubkey,
+    source_mint: Pubkey,
+    amount_in: u64,
+) -> Result<SwapSimulation> {
+    let dlmm_client = live_client(ctx, pair_key).await?;
+    let saros_dlmm = dlmm_client.saros_dlmm.read().await;
+
+    let pair = &saros_dlmm.pair;
+    if source_mint != pair.token_mint_x && source_mint != pair.token_mint_y {
+        bail!(AppError::InvalidInput(format!(
+            "Mint {} is not traded by pair {}",
+            source_mint, pair_key
+        )));
+    }
+    let destination_mint = if is_swap_for_y(source_mint, pair.token_mint_x) {
+        pair.token_mint_y
+    } else {
+        pair.token_mint_x
+    };
+
+    let quote = saros_dlmm
+        .quote(&QuoteParams {
+            amount: amount_in,
+            input_mint: source_mint,
+            output_mint: destination_mint,
+            swap_mode: SwapMode::ExactIn,
+        })
+        .map_err(|e| AppError::InsufficientLiquidity(e.to_string()))?;
+
+    let params = SwapInstructionParams {
+        user,
+        source_mint,
+        destination_mint,
+        source_token_program: ctx.get_mint_account(source_mint).await?.account.owner,
+        destination_token_program: ctx.get_mint_account(destination_mint).await?.account.owner,
+        amount: amount_in,
+        // Simulations report what the swap actually returns, so no minimum is enforced
+        other_amount_threshold: 0,
+        swap_mode: SwapMode::ExactIn,
+    };
+    let instruction = build_swap_instruction(&saros_dlmm, &params)?;
+
+    let watched_accounts = [
+        params.source_token_account(),
+        params.destination_token_account(),
+    ];
+    let pre_balances = simulator.token_balances(&watched_accounts).await?;
+
+    let blockhash = simulator.latest_blockhash().await?;
+    let transaction = unsigned_transaction(&user, &[instruction], blockhash)?;
+    let outcome = simulator.run(transaction, &watched_accounts).await?;
+
+    let balance_changes = [source_mint, destination_mint]
+        .iter()
+        .zip(watched_accounts)
+        .zip(pre_balances.iter().zip(outcome.post_balances.iter()))
+        .map(|((mint, token_account), (pre, post))| TokenBalanceChange {
+            mint: mint.to_string(),
+            token_account: token_account.to_string(),
+            pre_balance: *pre,
+            post_balance: *post,
+            change: *post as i128 - *pre as i128,
+        })
+        .collect();
+
+    Ok(SwapSimulation {
+        pair_address: pair_key.to_string(),
+        user: user.to_string(),
+        source_mint: source_mint.to_string(),
+        destination_mint: destination_mint.to_string(),
+        amount_in,
+        quoted_out_amount: quote.out_amount,
+        balance_changes,
+        units_consumed: outcome.units_consumed,
+        logs: outcome.logs,
+        error: outcome.error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signer::Signer;
+
+    use super::*;
+    use crate::test_support::TestBank;
+
+    #[tokio::test]
+    async fn simulated_swap_moves_the_quoted_amounts() -> Result<()> {
+        let amount_in = 1_000_000;
+        let Some(mut bank) = TestBank::start(amount_in * 10).await? else {
+            return Ok(());
+        };
+
+        for source_mint in [bank.pair.token_mint_x, bank.pair.token_mint_y] {
+            // A fresh app per swap, so the quote reads the pair as the previous swap left it
+            let ctx = bank.app_context().await?;
+            let simulator = BanksSwapSimulator {
+                banks_client: bank.context.banks_client.clone(),
+                payer: bank.user.insecure_clone(),
+            };
+
+            let simulation = simulate_swap(
+                &ctx,
+                &simulator,
+                bank.pair.address,
+                bank.user.pubkey(),
+                source_mint,
+                amount_in,
+            )
+            .await?;
+
+            assert_eq!(simulation.error, None, "{:#?}", simulation.logs);
+            assert_eq!(simulation.balance_changes[0].change, -(amount_in as i128));
+            assert_eq!(
+                simulation.balance_changes[1].change,
+                simulation.quoted_out_amount as i128
+            );
+        }
+
+        Ok(())
+    }
+}
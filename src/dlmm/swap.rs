@@ -0,0 +1,396 @@
+use anyhow::{ensure, Context, Result};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode, SwapParams};
+use saros_dlmm::SarosDlmm;
+use saros_sdk::utils::helper::is_swap_for_y;
+use serde::Serialize;
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account_client::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::native_mint;
+
+use crate::{
+    app::AppContext, dlmm::live_client, error::AppError, state::SwapDirection,
+    utils::anchor_discriminator,
+};
+
+/// Everything needed to build a swap for a user wallet, token programs are the owners of each mint
+pub struct SwapInstructionParams {
+    pub user: Pubkey,
+    pub source_mint: Pubkey,
+    pub destination_mint: Pubkey,
+    pub source_token_program: Pubkey,
+    pub destination_token_program: Pubkey,
+    /// Exact input for `ExactIn`, exact output for `ExactOut`
+    pub amount: u64,
+    /// Minimum output for `ExactIn`, maximum input for `ExactOut`
+    pub other_amount_threshold: u64,
+    pub swap_mode: SwapMode,
+}
+
+impl SwapInstructionParams {
+    pub fn source_token_account(&self) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            &self.user,
+            &self.source_mint,
+            &self.source_token_program,
+        )
+    }
+
+    pub fn destination_token_account(&self) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            &self.user,
+            &self.destination_mint,
+            &self.destination_token_program,
+        )
+    }
+}
+
+/// The user's wrapped SOL associated token account
+pub fn wrapped_sol_account(user: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(user, &native_mint::ID, &spl_token::ID)
+}
+
+/// Whether the user already holds a wrapped SOL account. Transactions leave such an account
+/// open: closing it would also unwrap the SOL the user keeps wrapped.
+pub async fn has_wrapped_sol_account(ctx: &AppContext, user: &Pubkey) -> Result<bool> {
+    let account = ctx
+        .accounts
+        .get_multiple_accounts(&[wrapped_sol_account(user)])
+        .await
+        .context("Failed to get wrapped SOL account")?
+        .into_iter()
+        .next()
+        .flatten();
+
+    Ok(account.is_some())
+}
+
+/// Builds the Saros `swap` instruction from the account metas the AMM exposes
+pub fn build_swap_instruction(
+    saros_dlmm: &SarosDlmm,
+    params: &SwapInstructionParams,
+) -> Result<Instruction> {
+    let program_id = saros_dlmm.program_id();
+    let (in_amount, out_amount) = match params.swap_mode {
+        SwapMode::ExactIn => (params.amount, params.other_amount_threshold),
+        SwapMode::ExactOut => (params.other_amount_threshold, params.amount),
+    };
+
+    let swap_and_account_metas = saros_dlmm.get_swap_and_account_metas(&SwapParams {
+        swap_mode: params.swap_mode,
+        in_amount,
+        out_amount,
+        source_mint: params.source_mint,
+        destination_mint: params.destination_mint,
+        source_token_account: params.source_token_account(),
+        destination_token_account: params.destination_token_account(),
+        token_transfer_authority: params.user,
+        quote_mint_to_referrer: None,
+        jupiter_program_id: &program_id,
+        missing_dynamic_accounts_as_default: false,
+    })?;
+
+    // The metas are laid out for a CPI, which passes the program itself first. Any other layout
+    // is one this encoding was not written for.
+    let mut accounts = swap_and_account_metas.account_metas;
+    ensure!(
+        accounts.first().map(|meta| meta.pubkey) == Some(program_id),
+        AppError::Internal(format!(
+            "Unexpected swap accounts for pair {}, the first is not the program",
+            saros_dlmm.key()
+        ))
+    );
+    accounts.remove(0);
+
+    let swap_for_y = is_swap_for_y(params.source_mint, saros_dlmm.pair.token_mint_x);
+    let swap_type: u8 = match params.swap_mode {
+        SwapMode::ExactIn => 0,
+        SwapMode::ExactOut => 1,
+    };
+
+    let mut data = anchor_discriminator("global:swap").to_vec();
+    data.extend_from_slice(&params.amount.to_le_bytes());
+    data.extend_from_slice(&params.other_amount_threshold.to_le_bytes());
+    data.push(swap_for_y as u8);
+    data.push(swap_type);
+
+    Ok(Instruction {
+        program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Surrounds the swap with everything a wallet needs to sign it as is: the destination token
+/// account is created if missing, and native SOL is wrapped before and unwrapped after the swap.
+/// SOL is only unwrapped when the transaction creates the wrapped SOL account, an account the
+/// user already has is left open with its balance.
+pub fn build_swap_instructions(
+    saros_dlmm: &SarosDlmm,
+    params: &SwapInstructionParams,
+    wrapped_sol_exists: bool,
+) -> Result<Vec<Instruction>> {
+    let source_token_account = params.source_token_account();
+    let destination_token_account = params.destination_token_account();
+    let mut instructions = vec![];
+
+    if params.source_mint == native_mint::ID {
+        let wrap_amount = match params.swap_mode {
+            SwapMode::ExactIn => params.amount,
+            SwapMode::ExactOut => params.other_amount_threshold,
+        };
+        instructions.push(create_associated_token_account_idempotent(
+            &params.user,
+            &params.user,
+            &params.source_mint,
+            &params.source_token_program,
+        ));
+        instructions.push(system_instruction::transfer(
+            &params.user,
+            &source_token_account,
+            wrap_amount,
+        ));
+        instructions.push(spl_token::instruction::sync_native(
+            &params.source_token_program,
+            &source_token_account,
+        )?);
+    }
+
+    instructions.push(create_associated_token_account_idempotent(
+        &params.user,
+        &params.user,
+        &params.destination_mint,
+        &params.destination_token_program,
+    ));
+    instructions.push(build_swap_instruction(saros_dlmm, params)?);
+
+    // Closing a wrapped SOL account returns its whole balance to the wallet as native SOL
+    if wrapped_sol_exists {
+        return Ok(instructions);
+    }
+    for (mint, token_account, token_program) in [
+        (
+            params.source_mint,
+            source_token_account,
+            params.source_token_program,
+        ),
+        (
+            params.destination_mint,
+            destination_token_account,
+            params.destination_token_program,
+        ),
+    ] {
+        if mint == native_mint::ID {
+            instructions.push(spl_token::instruction::close_account(
+                &token_program,
+                &token_account,
+                &params.user,
+                &params.user,
+                &[],
+            )?);
+        }
+    }
+
+    Ok(instructions)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuiltSwap {
+    /// Base64 encoded unsigned v0 transaction
+    pub transaction: String,
+    pub last_valid_block_height: u64,
+    pub source_mint: String,
+    pub destination_mint: String,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub min_out_amount: u64,
+    pub slippage_bps: u16,
+}
+
+/// Quotes an exact-in swap and returns it as an unsigned transaction for `user` to sign
+pub async fn build_swap_transaction(
+    ctx: &AppContext,
+    pair_key: Pubkey,
+    user: Pubkey,
+    direction: SwapDirection,
+    amount_in: u64,
+    slippage_bps: u16,
+) -> Result<BuiltSwap> {
+    ensure!(
+        slippage_bps <= 10_000,
+        AppError::InvalidInput("Slippage cannot exceed 10000 bps".to_string())
+    );
+
+    let dlmm_client = live_client(ctx, pair_key).await?;
+    let saros_dlmm = dlmm_client.saros_dlmm.read().await;
+
+    let (source_mint, destination_mint) = match direction {
+        SwapDirection::XToY => (saros_dlmm.pair.token_mint_x, saros_dlmm.pair.token_mint_y),
+        SwapDirection::YToX => (saros_dlmm.pair.token_mint_y, saros_dlmm.pair.token_mint_x),
+    };
+
+    let quote = saros_dlmm
+        .quote(&QuoteParams {
+            amount: amount_in,
+            input_mint: source_mint,
+            output_mint: destination_mint,
+            swap_mode: SwapMode::ExactIn,
+        })
+        .map_err(|e| AppError::InsufficientLiquidity(e.to_string()))?;
+    let min_out_amount =
+        (quote.out_amount as u128 * (10_000 - slippage_bps) as u128 / 10_000) as u64;
+
+    let params = SwapInstructionParams {
+        user,
+        source_mint,
+        destination_mint,
+        source_token_program: ctx.get_mint_account(source_mint).await?.account.owner,
+        destination_token_program: ctx.get_mint_account(destination_mint).await?.account.owner,
+        amount: amount_in,
+        other_amount_threshold: min_out_amount,
+        swap_mode: SwapMode::ExactIn,
+    };
+    let wrapped_sol_exists = (source_mint == native_mint::ID
+        || destination_mint == native_mint::ID)
+        && has_wrapped_sol_account(ctx, &user).await?;
+    let instructions = build_swap_instructions(&saros_dlmm, &params, wrapped_sol_exists)?;
+
+    let (blockhash, last_valid_block_height) = ctx
+        .rpc_client
+        .get_latest_blockhash_with_commitment(ctx.rpc_client.commitment())
+        .await?;
+
+    let transaction = unsigned_transaction(&user, &instructions, blockhash)?;
+
+    Ok(BuiltSwap {
+        transaction: STANDARD.encode(bincode::serialize(&transaction)?),
+        last_valid_block_height,
+        source_mint: source_mint.to_string(),
+        destination_mint: destination_mint.to_string(),
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+        min_out_amount,
+        slippage_bps,
+    })
+}
+
+/// Compiles the instructions into a v0 transaction paid by `payer`, with empty signatures
+pub fn unsigned_transaction(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = VersionedMessage::V0(v0::Message::try_compile(
+        payer,
+        instructions,
+        &[],
+        recent_blockhash,
+    )?);
+
+    Ok(VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    })
+}
+
+/// Amount held by an SPL Token or Token-2022 account, both share the base layout
+pub fn token_account_amount(data: &[u8]) -> Option<u64> {
+    data.get(64..72)
+        .map(|amount| u64::from_le_bytes(amount.try_into().unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::DLMM_PROGRAM_ID,
+        state::{bin_array_address, bin_array_index_of},
+        test_support::{bin_array_account, memory_context, mint_account, pair_account},
+    };
+
+    #[tokio::test]
+    async fn swap_instruction_encodes_the_anchor_swap_arguments() -> Result<()> {
+        let (pair, mint_x, mint_y, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let active_id = 8_388_608;
+        let mut accounts = vec![
+            (
+                pair,
+                pair_account(|pair| {
+                    pair.token_mint_x = mint_x;
+                    pair.token_mint_y = mint_y;
+                    pair.bin_step = 20;
+                    pair.active_id = active_id;
+                }),
+            ),
+            (mint_x, mint_account(6)),
+            (mint_y, mint_account(6)),
+        ];
+        let index = bin_array_index_of(active_id);
+        for index in index - 1..=index + 1 {
+            accounts.push((
+                bin_array_address(&pair, &DLMM_PROGRAM_ID, index),
+                bin_array_account(index, |_| (1_000_000, 1_000_000)),
+            ));
+        }
+        let ctx = memory_context(accounts);
+        let client = ctx.get_or_spawn_client(pair).await?;
+        let saros_dlmm = client.saros_dlmm.read().await;
+
+        let params = SwapInstructionParams {
+            user,
+            source_mint: mint_y,
+            destination_mint: mint_x,
+            source_token_program: spl_token::ID,
+            destination_token_program: spl_token::ID,
+            amount: 500,
+            other_amount_threshold: 1_000,
+            swap_mode: SwapMode::ExactOut,
+        };
+        let instruction = build_swap_instruction(&saros_dlmm, &params)?;
+
+        assert_eq!(instruction.program_id, DLMM_PROGRAM_ID);
+        // `swap(amount: u64, other_amount_threshold: u64, swap_for_y: bool, swap_type: u8)`
+        // behind the Anchor tag of `swap`, with swap type 1 for exact out
+        let mut expected = vec![248, 198, 158, 145, 225, 117, 135, 200];
+        expected.extend_from_slice(&500u64.to_le_bytes());
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        expected.extend_from_slice(&[0, 1]);
+        assert_eq!(instruction.data, expected);
+
+        let meta = |key: Pubkey| {
+            instruction
+                .accounts
+                .iter()
+                .find(|meta| meta.pubkey == key)
+                .cloned()
+        };
+        assert!(meta(pair).is_some_and(|meta| meta.is_writable));
+        assert!(meta(user).is_some_and(|meta| meta.is_signer));
+        for token_account in [
+            params.source_token_account(),
+            params.destination_token_account(),
+        ] {
+            assert!(meta(token_account).is_some_and(|meta| meta.is_writable && !meta.is_signer));
+        }
+        assert_ne!(instruction.accounts[0].pubkey, DLMM_PROGRAM_ID);
+
+        Ok(())
+    }
+}
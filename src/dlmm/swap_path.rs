@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use saros_sdk::state::{bin_array::BinArray, pair::Pair};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dlmm::fees::{base_fee, variable_fee, FEE_PRECISION},
+    pricing::price_from_id,
+    state::{bin_array_index_of, BIN_ARRAY_SIZE},
+};
+
+const BASIS_POINT_MAX: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapPathStep {
+    pub bin_id: u32,
+    /// Price of X in Y for the bin
+    pub price: f64,
+    /// Input taken by the bin, fee included
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapPath {
+    pub start_active_id: u32,
+    pub final_active_id: u32,
+    pub steps: Vec<SwapPathStep>,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    /// Fixed amount that could not be swapped within the loaded bin arrays
+    pub amount_left: u64,
+}
+
+/// Volatility reference and id reference as the program would see them at `now`
+fn fee_references(pair: &Pair, now: u64) -> (u32, u32) {
+    let static_fee = &pair.static_fee_parameters;
+    let dynamic_fee = &pair.dynamic_fee_parameters;
+    let elapsed = now.saturating_sub(dynamic_fee.time_last_updated);
+
+    if elapsed < static_fee.filter_period as u64 {
+        return (dynamic_fee.volatility_reference, dynamic_fee.id_reference);
+    }
+
+    let volatility_reference = if elapsed < static_fee.decay_period as u64 {
+        (dynamic_fee.volatility_accumulator as u64 * static_fee.reduction_factor as u64
+            / BASIS_POINT_MAX) as u32
+    } else {
+        0
+    };
+    (volatility_reference, pair.active_id)
+}
+
+/// Replays a swap bin by bin from the pair's active id, the same way the program walks the bins.
+/// Prices are evaluated in floating point, so amounts can differ from the quote by rounding.
+pub fn replay_swap(
+    pair: &Pair,
+    bin_arrays: &HashMap<u32, BinArray>,
+    swap_for_y: bool,
+    exact_in: bool,
+    amount: u64,
+    now: u64,
+) -> SwapPath {
+    let (volatility_reference, id_reference) = fee_references(pair, now);
+    let max_volatility_accumulator = pair.static_fee_parameters.max_volatility_accumulator as u64;
+
+    let mut active_id = pair.active_id;
+    let mut amount_left = amount as u128;
+    let mut steps = vec![];
+
+    while amount_left > 0 {
+        let Some(bin_array) = bin_arrays.get(&bin_array_index_of(active_id)) else {
+            break;
+        };
+        let bin = &bin_array.bins[(active_id % BIN_ARRAY_SIZE) as usize];
+        let reserve_out = (if swap_for_y {
+            bin.reserve_y
+        } else {
+            bin.reserve_x
+        }) as u128;
+
+        if reserve_out > 0 {
+            let volatility_accumulator = (volatility_reference as u64
+                + active_id.abs_diff(id_reference) as u64 * BASIS_POINT_MAX)
+                .min(max_volatility_accumulator) as u32;
+            let fee_rate = (base_fee(pair) + variable_fee(pair, volatility_accumulator))
+                .min(FEE_PRECISION / 10) as u128;
+
+            let price = price_from_id(pair.bin_step, active_id);
+            let to_out = |amount_in: u128| -> u128 {
+                let out = if swap_for_y {
+                    amount_in as f64 * price
+                } else {
+                    amount_in as f64 / price
+                };
+                out.floor() as u128
+            };
+            let to_in = |amount_out: u128| -> u128 {
+                let amount_in = if swap_for_y {
+                    amount_out as f64 / price
+                } else {
+                    amount_out as f64 * price
+                };
+                amount_in.ceil() as u128
+            };
+            let fee_on_top = |amount: u128| -> u128 {
+                (amount * fee_rate).div_ceil(FEE_PRECISION as u128 - fee_rate)
+            };
+
+            let (amount_in, amount_out, fee) = if exact_in {
+                let max_in = to_in(reserve_out);
+                let max_fee = fee_on_top(max_in);
+                if amount_left >= max_in + max_fee {
+                    (max_in + max_fee, reserve_out, max_fee)
+                } else {
+                    let fee = (amount_left * fee_rate).div_ceil(FEE_PRECISION as u128);
+                    let amount_out = to_out(amount_left - fee).min(reserve_out);
+                    (amount_left, amount_out, fee)
+                }
+            } else {
+                let amount_out = amount_left.min(reserve_out);
+                let amount_in = to_in(amount_out);
+                let fee = fee_on_top(amount_in);
+                (amount_in + fee, amount_out, fee)
+            };
+
+            amount_left -= if exact_in { amount_in } else { amount_out };
+            steps.push(SwapPathStep {
+                bin_id: active_id,
+                price,
+                amount_in: amount_in as u64,
+                amount_out: amount_out as u64,
+                fee: fee as u64,
+            });
+        }
+
+        if amount_left == 0 {
+            break;
+        }
+
+        active_id = if swap_for_y {
+            match active_id.checked_sub(1) {
+                Some(id) => id,
+                None => break,
+            }
+        } else {
+            active_id + 1
+        };
+    }
+
+    SwapPath {
+        start_active_id: pair.active_id,
+        final_active_id: active_id,
+        amount_in: steps.iter().map(|step| step.amount_in).sum(),
+        amount_out: steps.iter().map(|step| step.amount_out).sum(),
+        fee: steps.iter().map(|step| step.fee).sum(),
+        steps,
+        amount_left: amount_left as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, sync::Arc};
+
+    use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+    use super::*;
+    use crate::{
+        app::{AppContext, Cached, DLMM_PROGRAM_ID},
+        dlmm::{quote_pair, QuoteInput},
+        state::{bin_array_address, QuoteResponse, QuoteSwapMode, TokenMeta},
+        test_support::{bin_array_account, memory_context, mint_account, pair_account},
+    };
+
+    /// In the middle of its bin array, so swaps cross several bins without leaving it
+    const ACTIVE_ID: u32 = 8_388_608 + BIN_ARRAY_SIZE / 2;
+    const BIN_RESERVE: u64 = 1_000_000_000;
+
+    struct Market {
+        ctx: Arc<AppContext>,
+        pair: Pubkey,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+    }
+
+    /// A pair holding `BIN_RESERVE` of Y in the active bin and every bin below it, and of X in
+    /// the active bin and every bin above it. Its variable fee grows with every bin crossed.
+    async fn market() -> Market {
+        let (pair, mint_x, mint_y) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let pair_account = pair_account(|pair| {
+            pair.token_mint_x = mint_x;
+            pair.token_mint_y = mint_y;
+            pair.bin_step = 20;
+            pair.active_id = ACTIVE_ID;
+            pair.static_fee_parameters.base_factor = 10_000;
+            pair.static_fee_parameters.filter_period = 30;
+            pair.static_fee_parameters.decay_period = 600;
+            pair.static_fee_parameters.reduction_factor = 5_000;
+            pair.static_fee_parameters.variable_fee_control = 40_000;
+            pair.static_fee_parameters.max_volatility_accumulator = 350_000;
+            pair.dynamic_fee_parameters.id_reference = ACTIVE_ID;
+        });
+        let reserves = |id: u32| match id.cmp(&ACTIVE_ID) {
+            Ordering::Less => (0, BIN_RESERVE),
+            Ordering::Equal => (BIN_RESERVE, BIN_RESERVE),
+            Ordering::Greater => (BIN_RESERVE, 0),
+        };
+
+        let mut accounts = vec![
+            (pair, pair_account),
+            (mint_x, mint_account(6)),
+            (mint_y, mint_account(6)),
+        ];
+        let index = bin_array_index_of(ACTIVE_ID);
+        for index in index - 1..=index + 1 {
+            accounts.push((
+                bin_array_address(&pair, &DLMM_PROGRAM_ID, index),
+                bin_array_account(index, reserves),
+            ));
+        }
+        let ctx = memory_context(accounts);
+
+        // The mints have no metadata account to read symbols from
+        for (mint, symbol) in [(mint_x, "X"), (mint_y, "Y")] {
+            let token = TokenMeta {
+                mint,
+                symbol: symbol.to_string(),
+                decimals: 6,
+            };
+            ctx.token_meta_cache
+                .write()
+                .await
+                .insert(mint, Cached::new(token));
+        }
+
+        Market {
+            ctx,
+            pair,
+            mint_x,
+            mint_y,
+        }
+    }
+
+    async fn quote(
+        market: &Market,
+        swap_for_y: bool,
+        swap_mode: QuoteSwapMode,
+        amount: u64,
+    ) -> QuoteResponse {
+        let (source_mint, destination_mint) = if swap_for_y {
+            (market.mint_x, market.mint_y)
+        } else {
+            (market.mint_y, market.mint_x)
+        };
+        let input = QuoteInput {
+            source_mint,
+            destination_mint,
+            amount,
+            swap_mode,
+            slippage_bps: 0,
+            verbose: true,
+        };
+
+        let client = market.ctx.get_or_spawn_client(market.pair).await.unwrap();
+        let saros_dlmm = client.saros_dlmm.read().await;
+        quote_pair(&market.ctx, &saros_dlmm, &input).await.unwrap()
+    }
+
+    /// The path prices each bin in floating point, the quote in fixed point: they may round
+    /// apart by a unit per bin
+    fn assert_close(path: u64, quote: u64, bins: usize, what: &str) {
+        assert!(
+            path.abs_diff(quote) <= bins as u64,
+            "{}: the path says {}, the quote {}",
+            what,
+            path,
+            quote
+        );
+    }
+
+    /// Checks the path against the quote of the whole amount, then against the quote of what
+    /// the first bins took, one more bin at a time
+    async fn assert_path_matches_quotes(swap_for_y: bool, swap_mode: QuoteSwapMode) {
+        let market = market().await;
+        let exact_in = swap_mode == QuoteSwapMode::ExactIn;
+        // Four and a half bins worth of the destination token, or about as much source
+        let amount = BIN_RESERVE * 9 / 2;
+
+        let quoted = quote(&market, swap_for_y, swap_mode, amount).await;
+        let path = quoted
+            .path
+            .clone()
+            .expect("verbose quotes carry their path");
+        assert!(path.steps.len() >= 4, "{} bins crossed", path.steps.len());
+        assert_eq!(path.amount_left, 0);
+        assert_eq!(path.start_active_id, ACTIVE_ID);
+        assert_eq!(path.final_active_id, path.steps.last().unwrap().bin_id);
+
+        // Bins are walked one after the other away from the active bin, emptying all but the last
+        for (offset, step) in path.steps.iter().enumerate() {
+            let offset = offset as u32;
+            let bin_id = if swap_for_y {
+                ACTIVE_ID - offset
+            } else {
+                ACTIVE_ID + offset
+            };
+            assert_eq!(step.bin_id, bin_id);
+        }
+        let (last, emptied) = path.steps.split_last().unwrap();
+        assert!(emptied.iter().all(|step| step.amount_out == BIN_RESERVE));
+        assert!(last.amount_out <= BIN_RESERVE);
+
+        let bins = path.steps.len();
+        assert_close(path.amount_in, quoted.in_amount, bins, "amount in");
+        assert_close(path.amount_out, quoted.out_amount, bins, "amount out");
+        assert_close(path.fee, quoted.fee_amount, bins, "fee");
+
+        // Fees grow away from the active bin with the volatility the swap adds
+        assert!(
+            emptied.windows(2).all(|pair| pair[0].fee < pair[1].fee),
+            "{:?}",
+            emptied
+        );
+
+        for bins in 1..path.steps.len() {
+            let steps = &path.steps[..bins];
+            let amount_in: u64 = steps.iter().map(|step| step.amount_in).sum();
+            let amount_out: u64 = steps.iter().map(|step| step.amount_out).sum();
+            let fee: u64 = steps.iter().map(|step| step.fee).sum();
+
+            let fixed = if exact_in { amount_in } else { amount_out };
+            let partial = quote(&market, swap_for_y, swap_mode, fixed).await;
+            let what = |field| format!("{} over the first {} bins", field, bins);
+            assert_close(amount_in, partial.in_amount, bins, &what("amount in"));
+            assert_close(amount_out, partial.out_amount, bins, &what("amount out"));
+            assert_close(fee, partial.fee_amount, bins, &what("fee"));
+        }
+    }
+
+    #[tokio::test]
+    async fn replayed_bins_match_the_quote_selling_x_exact_in() {
+        assert_path_matches_quotes(true, QuoteSwapMode::ExactIn).await;
+    }
+
+    #[tokio::test]
+    async fn replayed_bins_match_the_quote_selling_x_exact_out() {
+        assert_path_matches_quotes(true, QuoteSwapMode::ExactOut).await;
+    }
+
+    #[tokio::test]
+    async fn replayed_bins_match_the_quote_buying_x_exact_in() {
+        assert_path_matches_quotes(false, QuoteSwapMode::ExactIn).await;
+    }
+
+    #[tokio::test]
+    async fn replayed_bins_match_the_quote_buying_x_exact_out() {
+        assert_path_matches_quotes(false, QuoteSwapMode::ExactOut).await;
+    }
+
+    #[test]
+    fn a_swap_stops_where_the_loaded_bin_arrays_end() {
+        let pair_account = pair_account(|pair| {
+            pair.bin_step = 20;
+            pair.active_id = ACTIVE_ID;
+        });
+        let pair = Pair::unpack_unchecked(&pair_account.data).unwrap();
+        let bin_array = BinArray::unpack_unchecked(
+            &bin_array_account(bin_array_index_of(ACTIVE_ID), |_| {
+                (BIN_RESERVE, BIN_RESERVE)
+            })
+            .data,
+        )
+        .unwrap();
+        let bin_arrays = HashMap::from([(bin_array_index_of(ACTIVE_ID), bin_array)]);
+
+        // Every bin up to the end of the array is emptied, the rest is left over
+        let bins_left = BIN_ARRAY_SIZE - ACTIVE_ID % BIN_ARRAY_SIZE;
+        let amount = BIN_RESERVE * (bins_left as u64 + 2);
+        let path = replay_swap(&pair, &bin_arrays, false, false, amount, 0);
+
+        assert_eq!(path.steps.len() as u32, bins_left);
+        assert_eq!(path.amount_out, BIN_RESERVE * bins_left as u64);
+        assert_eq!(path.amount_left, BIN_RESERVE * 2);
+    }
+}
@@ -0,0 +1,126 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        FromRequest, FromRequestParts, Query,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use solana_client::{client_error::ClientError, nonblocking::pubsub_client::PubsubClientError};
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::state::{Status, WebJsonResponse};
+
+/// Errors surfaced to API clients. Each variant has a stable `code` and an HTTP status,
+/// so clients can branch on the kind of failure without parsing the message.
+#[derive(Debug, Clone, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Pair {0} not found")]
+    PairNotFound(Pubkey),
+    #[error("RPC unavailable: {0}")]
+    RpcUnavailable(String),
+    #[error("Failed to decode {0}")]
+    DecodeFailure(String),
+    #[error("Insufficient liquidity: {0}")]
+    InsufficientLiquidity(String),
+    /// The data asked for is still being loaded, e.g. the pair registry right after startup
+    #[error("{0}")]
+    NotReady(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::PairNotFound(_) => "pair_not_found",
+            AppError::RpcUnavailable(_) => "rpc_unavailable",
+            AppError::DecodeFailure(_) => "decode_failure",
+            AppError::InsufficientLiquidity(_) => "insufficient_liquidity",
+            AppError::NotReady(_) => "not_ready",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::PairNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::InsufficientLiquidity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::RpcUnavailable(_) => StatusCode::BAD_GATEWAY,
+            AppError::NotReady(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::DecodeFailure(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// `error` when the request has to change, `failure` when the server could not serve it
+    pub fn to_response(&self) -> WebJsonResponse {
+        let status = if self.status_code().is_server_error() {
+            Status::Failure
+        } else {
+            Status::Error
+        };
+
+        WebJsonResponse {
+            status,
+            message: self.to_string(),
+            data: json!({ "code": self.code() }),
+        }
+    }
+}
+
+/// Keeps the first `AppError` raised down the chain, RPC client errors become
+/// `RpcUnavailable` and anything else is `Internal`
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        if let Some(app_error) = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<AppError>())
+        {
+            return app_error.clone();
+        }
+
+        let from_rpc = error
+            .chain()
+            .any(|cause| cause.is::<ClientError>() || cause.is::<PubsubClientError>());
+        if from_rpc {
+            AppError::RpcUnavailable(format!("{:#}", error))
+        } else {
+            AppError::Internal(format!("{:#}", error))
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status_code(), Json(self.to_response())).into_response()
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::InvalidInput(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::InvalidInput(rejection.body_text())
+    }
+}
+
+/// `Json` extractor answering malformed or missing bodies with `invalid_input`
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/// `Query` extractor answering malformed or missing parameters with `invalid_input`
+#[derive(FromRequestParts)]
+#[from_request(via(Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
@@ -1,13 +1,17 @@
 mod app;
 mod cli;
+mod commands;
 mod dlmm;
+mod error;
+mod pricing;
 mod state;
+#[cfg(test)]
+mod test_support;
+mod utils;
 mod web;
 
-use std::time::Duration;
-
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, PairCommands, PositionCommands};
 use tracing::info;
 
 #[tokio::main]
@@ -18,43 +22,126 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let mut config = app::AppConfig::from_env();
 
     match cli.command {
-        Commands::Start { web } => {
+        Commands::Start {
+            web,
+            record,
+            replay,
+        } => {
             info!("🚀 Starting Saros DLMM Interface...");
-            let mut config = app::AppConfig::default();
-
-            if let Ok(rpc_url) = dotenv::var("RPC_URL") {
-                info!("Using RPC URL from .env: {}", rpc_url);
-                config.rpc_url = rpc_url;
-            }
-
-            if let Ok(pool_ttl_secs) = dotenv::var("POOL_CACHE_TTL_SECS") {
-                if let Ok(pool_ttl) = pool_ttl_secs.parse::<u64>() {
-                    info!("Using Pool Cache TTL from .env: {} seconds", pool_ttl);
-                    config.cache_ttl.pool_ttl = Duration::from_secs(pool_ttl);
-                }
-            }
-
-            if let Ok(token_ttl_secs) = dotenv::var("TOKEN_CACHE_TTL_SECS") {
-                if let Ok(token_ttl) = token_ttl_secs.parse::<u64>() {
-                    info!("Using Token Cache TTL from .env: {} seconds", token_ttl);
-                    config.cache_ttl.token_ttl = Duration::from_secs(token_ttl);
-                }
-            }
-
-            if let Ok(bin_ttl_secs) = dotenv::var("BIN_CACHE_TTL_SECS") {
-                if let Ok(bin_ttl) = bin_ttl_secs.parse::<u64>() {
-                    info!("Using Bin Cache TTL from .env: {} seconds", bin_ttl);
-                    config.cache_ttl.bin_ttl = Duration::from_secs(bin_ttl);
-                }
+            if replay.is_some() {
+                config.fixtures_dir = replay;
             }
+            config.record_dir = record;
             if web {
                 web::start_web_server(config).await?;
             } else {
                 info!("Running in CLI-only mode...");
             }
         }
+        Commands::Quote(args) => {
+            let ctx = app::AppContext::new(config);
+            commands::quote::run(&ctx, args).await?;
+        }
+        Commands::Pair { command } => {
+            if let PairCommands::Record { dir, .. } = &command {
+                config.record_dir = Some(dir.clone());
+            }
+            let ctx = app::AppContext::new(config);
+            match command {
+                PairCommands::Inspect { address, json } => {
+                    commands::pair::inspect(&ctx, address, json).await?;
+                }
+                PairCommands::Find {
+                    mint_a,
+                    mint_b,
+                    json,
+                } => {
+                    commands::pair::find(&ctx, mint_a, mint_b, json).await?;
+                }
+                PairCommands::Bins {
+                    address,
+                    from,
+                    to,
+                    json,
+                } => {
+                    commands::pair::bins(&ctx, address, from, to, json).await?;
+                }
+                PairCommands::Record { address, .. } => {
+                    commands::pair::record(&ctx, address).await?;
+                }
+            }
+        }
+        Commands::Position { command } => {
+            let ctx = app::AppContext::new(config);
+            match command {
+                PositionCommands::List { owner, json } => {
+                    commands::position::list(&ctx, owner, json).await?;
+                }
+                PositionCommands::Show {
+                    address,
+                    entry_amount_x,
+                    entry_amount_y,
+                    entry_price,
+                    json,
+                } => {
+                    let entry = state::PositionEntry::from_parts(
+                        entry_amount_x,
+                        entry_amount_y,
+                        entry_price,
+                    )?;
+                    commands::position::show(&ctx, address, entry, json).await?;
+                }
+                PositionCommands::Create {
+                    pair,
+                    user,
+                    lower_bin_id,
+                    upper_bin_id,
+                    min_price,
+                    max_price,
+                    amount_x,
+                    amount_y,
+                    shape,
+                    json,
+                } => {
+                    let range = state::PositionRange::from_parts(
+                        lower_bin_id,
+                        upper_bin_id,
+                        min_price,
+                        max_price,
+                    )?;
+                    commands::position::create(
+                        &ctx, pair, user, range, amount_x, amount_y, shape, json,
+                    )
+                    .await?;
+                }
+                PositionCommands::Add {
+                    address,
+                    user,
+                    amount_x,
+                    amount_y,
+                    shape,
+                    json,
+                } => {
+                    commands::position::add(&ctx, address, user, amount_x, amount_y, shape, json)
+                        .await?;
+                }
+                PositionCommands::Remove {
+                    address,
+                    user,
+                    bps,
+                    bins,
+                    json,
+                } => {
+                    commands::position::remove(&ctx, address, user, bps, bins, json).await?;
+                }
+                PositionCommands::Claim { user, json } => {
+                    commands::position::claim(&ctx, user, json).await?;
+                }
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,182 @@
+//! Conversions between bin ids and prices. A price is always the price of one token X in token
+//! Y, either in raw units or in UI units, i.e. adjusted for the decimals of both tokens.
+
+use crate::state::TokenMeta;
+
+/// Bin id that maps to a price of exactly 1
+pub const BIN_ID_OFFSET: u32 = 1 << 23;
+
+/// Prices this close to a bin's price, in bins, are taken as that bin whatever the rounding,
+/// so float noise does not push an exact bin price to its neighbour
+const BIN_EPSILON: f64 = 1e-9;
+
+/// Which bin a price falling between two bins maps to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// The bin below, whose price does not exceed the given price
+    Down,
+    /// The bin above, whose price is not below the given price
+    Up,
+}
+
+fn bin_base(bin_step: u8) -> f64 {
+    1.0 + bin_step as f64 / 10_000.0
+}
+
+/// Raw price of a bin, `(1 + bin_step / 10_000) ^ (bin_id - 2^23)`
+pub fn price_from_id(bin_step: u8, bin_id: u32) -> f64 {
+    bin_base(bin_step).powf(bin_id as f64 - BIN_ID_OFFSET as f64)
+}
+
+/// Bin id of a raw price, the inverse of `price_from_id`. `None` for prices that are not
+/// positive or fall outside of the bin ids.
+pub fn id_from_price(bin_step: u8, price: f64, rounding: Rounding) -> Option<u32> {
+    if !price.is_finite() || price <= 0.0 || bin_step == 0 {
+        return None;
+    }
+
+    let bins = price.ln() / bin_base(bin_step).ln();
+    let nearest = bins.round();
+    let bins = if (bins - nearest).abs() < BIN_EPSILON {
+        nearest
+    } else {
+        match rounding {
+            Rounding::Down => bins.floor(),
+            Rounding::Up => bins.ceil(),
+        }
+    };
+
+    let id = bins + BIN_ID_OFFSET as f64;
+    (id >= 0.0 && id <= u32::MAX as f64).then_some(id as u32)
+}
+
+/// Adjusts a raw price for the decimals of both tokens
+pub fn to_ui_price(price: f64, decimals_x: u8, decimals_y: u8) -> f64 {
+    price * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+}
+
+/// Converts a UI price back to raw units, the inverse of `to_ui_price`
+pub fn from_ui_price(price: f64, decimals_x: u8, decimals_y: u8) -> f64 {
+    price / 10f64.powi(decimals_x as i32 - decimals_y as i32)
+}
+
+/// UI price of a bin
+pub fn ui_price_from_id(
+    bin_step: u8,
+    bin_id: u32,
+    token_x: &TokenMeta,
+    token_y: &TokenMeta,
+) -> f64 {
+    to_ui_price(
+        price_from_id(bin_step, bin_id),
+        token_x.decimals,
+        token_y.decimals,
+    )
+}
+
+/// Bin id of a UI price, the inverse of `ui_price_from_id`
+pub fn id_from_ui_price(
+    bin_step: u8,
+    price: f64,
+    token_x: &TokenMeta,
+    token_y: &TokenMeta,
+    rounding: Rounding,
+) -> Option<u32> {
+    id_from_price(
+        bin_step,
+        from_ui_price(price, token_x.decimals, token_y.decimals),
+        rounding,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIN_STEPS: [u8; 9] = [1, 2, 5, 10, 20, 25, 50, 80, 100];
+
+    /// Ids around the unit price and far on both sides of it
+    fn bin_ids(bin_step: u8) -> impl Iterator<Item = u32> {
+        // Prices stay within f64 range up to about 700 / ln(1 + bin_step / 10_000) bins away
+        let reach = (700.0 / bin_base(bin_step).ln()) as u32;
+        let far = [BIN_ID_OFFSET - reach, BIN_ID_OFFSET + reach];
+        (BIN_ID_OFFSET - 300..=BIN_ID_OFFSET + 300)
+            .chain(far.into_iter().flat_map(|id| id - 5..=id + 5))
+            .chain((1..=20).map(move |i| BIN_ID_OFFSET - reach + i * reach / 10))
+    }
+
+    #[test]
+    fn ids_round_trip_through_their_price() {
+        for bin_step in BIN_STEPS {
+            for id in bin_ids(bin_step) {
+                let price = price_from_id(bin_step, id);
+                for rounding in [Rounding::Down, Rounding::Up] {
+                    assert_eq!(
+                        id_from_price(bin_step, price, rounding),
+                        Some(id),
+                        "bin step {}, id {}, {:?}",
+                        bin_step,
+                        id,
+                        rounding
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ids_round_trip_through_their_ui_price() {
+        let token = |decimals| TokenMeta {
+            decimals,
+            ..TokenMeta::default()
+        };
+
+        for (decimals_x, decimals_y) in [(9, 6), (6, 9), (6, 6), (0, 9), (9, 0)] {
+            let (token_x, token_y) = (token(decimals_x), token(decimals_y));
+            for bin_step in BIN_STEPS {
+                for id in BIN_ID_OFFSET - 1_000..=BIN_ID_OFFSET + 1_000 {
+                    let price = ui_price_from_id(bin_step, id, &token_x, &token_y);
+                    assert_eq!(
+                        id_from_ui_price(bin_step, price, &token_x, &token_y, Rounding::Down),
+                        Some(id),
+                        "decimals {}/{}, bin step {}, id {}",
+                        decimals_x,
+                        decimals_y,
+                        bin_step,
+                        id
+                    );
+                }
+            }
+
+            let raw = 1.2345e-3;
+            let ui = to_ui_price(raw, decimals_x, decimals_y);
+            assert!((from_ui_price(ui, decimals_x, decimals_y) / raw - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn prices_between_bins_round_to_either_neighbour() {
+        for bin_step in BIN_STEPS {
+            for id in [BIN_ID_OFFSET - 1_000, BIN_ID_OFFSET, BIN_ID_OFFSET + 1_000] {
+                let (low, high) = (price_from_id(bin_step, id), price_from_id(bin_step, id + 1));
+                for between in [
+                    (low * high).sqrt(),
+                    low + (high - low) * 0.01,
+                    high - (high - low) * 0.01,
+                ] {
+                    assert_eq!(id_from_price(bin_step, between, Rounding::Down), Some(id));
+                    assert_eq!(id_from_price(bin_step, between, Rounding::Up), Some(id + 1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prices_outside_the_bins_have_no_id() {
+        for price in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(id_from_price(10, price, Rounding::Down), None);
+        }
+        assert_eq!(id_from_price(0, 1.0, Rounding::Down), None);
+        assert_eq!(id_from_price(10, 1.0, Rounding::Up), Some(BIN_ID_OFFSET));
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures_util::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_account_decoder::{encode_ui_account, UiAccount, UiAccountEncoding};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_request::RpcRequest,
+    rpc_response::{Response, RpcKeyedAccount},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// Maximum number of accounts a single `getMultipleAccounts` call accepts
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Token accounts start with the mint, followed by the owner
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+/// Matches accounts holding `bytes` at `offset` in their data, like the RPC `memcmp` filter
+#[derive(Clone, Debug)]
+pub struct DataFilter {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl DataFilter {
+    pub fn new(offset: usize, bytes: &[u8]) -> Self {
+        DataFilter {
+            offset,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.get(self.offset..self.offset + self.bytes.len()) == Some(self.bytes.as_slice())
+    }
+}
+
+fn matches_all(account: &Account, program_id: &Pubkey, filters: &[DataFilter]) -> bool {
+    account.owner == *program_id && filters.iter().all(|filter| filter.matches(&account.data))
+}
+
+/// Where account data comes from: a live RPC node, an in-memory map or a fixture directory.
+/// Every read also returns the slot the accounts were read at.
+#[async_trait]
+pub trait AccountSource: Send + Sync {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)>;
+
+    /// Accounts owned by `program_id` whose data matches every filter
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)>;
+
+    /// Token accounts of `token_program` held by `owner`
+    async fn get_token_accounts_by_owner_with_slot(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        self.get_program_accounts_with_slot(
+            token_program,
+            &[DataFilter::new(TOKEN_ACCOUNT_OWNER_OFFSET, owner.as_ref())],
+        )
+        .await
+    }
+
+    /// Fails when the account does not exist, like `RpcClient::get_account`
+    async fn get_account(&self, key: &Pubkey) -> Result<Account> {
+        let (_, accounts) = self.get_multiple_accounts_with_slot(&[*key]).await?;
+        accounts
+            .into_iter()
+            .next()
+            .flatten()
+            .with_context(|| format!("Account {} not found", key))
+    }
+
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(self.get_multiple_accounts_with_slot(keys).await?.1)
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        Ok(self
+            .get_program_accounts_with_slot(program_id, filters)
+            .await?
+            .1)
+    }
+
+    async fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        Ok(self
+            .get_token_accounts_by_owner_with_slot(owner, token_program)
+            .await?
+            .1)
+    }
+
+    /// Whether the accounts can change, so that it is worth subscribing to them
+    fn is_live(&self) -> bool {
+        false
+    }
+
+    /// Sees account updates that reached the app without going through the source,
+    /// such as subscription notifications
+    async fn observe(&self, _key: &Pubkey, _account: &Account, _slot: u64) {}
+}
+
+pub struct RpcAccountSource {
+    pub client: Arc<RpcClient>,
+}
+
+#[async_trait]
+impl AccountSource for RpcAccountSource {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let commitment = self.client.commitment();
+        let responses = try_join_all(keys.chunks(MAX_MULTIPLE_ACCOUNTS).map(|chunk| {
+            self.client
+                .get_multiple_accounts_with_commitment(chunk, commitment)
+        }))
+        .await?;
+
+        let slot = responses
+            .iter()
+            .map(|response| response.context.slot)
+            .max()
+            .unwrap_or_default();
+        let accounts = responses
+            .into_iter()
+            .flat_map(|response| response.value)
+            .collect();
+
+        Ok((slot, accounts))
+    }
+
+    /// `getProgramAccounts` does not report a context, so the slot is read right before the scan
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(
+                filters
+                    .iter()
+                    .map(|filter| {
+                        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                            filter.offset,
+                            filter.bytes.clone(),
+                        ))
+                    })
+                    .collect(),
+            ),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let slot = self.client.get_slot().await?;
+        let accounts = self
+            .client
+            .get_program_accounts_with_config(program_id, config)
+            .await?;
+
+        Ok((slot, accounts))
+    }
+
+    /// `getTokenAccountsByOwner` is indexed by most providers, unlike a scan of the token program
+    async fn get_token_accounts_by_owner_with_slot(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.client.commitment()),
+            ..Default::default()
+        };
+        let response: Response<Vec<RpcKeyedAccount>> = self
+            .client
+            .send(
+                RpcRequest::GetTokenAccountsByOwner,
+                json!([
+                    owner.to_string(),
+                    { "programId": token_program.to_string() },
+                    config
+                ]),
+            )
+            .await?;
+
+        let mut accounts = vec![];
+        for keyed_account in response.value {
+            let key = keyed_account.pubkey.parse::<Pubkey>()?;
+            let account = keyed_account
+                .account
+                .decode::<Account>()
+                .ok_or_else(|| anyhow!("Failed to decode token account {}", key))?;
+            accounts.push((key, account));
+        }
+
+        Ok((response.context.slot, accounts))
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+}
+
+/// Accounts held in memory, to run tests against a known state. They have no slot,
+/// reads report slot 0.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryAccountSource {
+    accounts: std::sync::RwLock<HashMap<Pubkey, Account>>,
+}
+
+#[cfg(test)]
+impl MemoryAccountSource {
+    pub fn new(accounts: HashMap<Pubkey, Account>) -> Self {
+        MemoryAccountSource {
+            accounts: std::sync::RwLock::new(accounts),
+        }
+    }
+
+    pub fn insert(&self, key: Pubkey, account: Account) {
+        self.accounts
+            .write()
+            .expect("account map poisoned")
+            .insert(key, account);
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl AccountSource for MemoryAccountSource {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let accounts = self.accounts.read().expect("account map poisoned");
+        Ok((
+            0,
+            keys.iter().map(|key| accounts.get(key).cloned()).collect(),
+        ))
+    }
+
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let accounts = self.accounts.read().expect("account map poisoned");
+        Ok((
+            0,
+            accounts
+                .iter()
+                .filter(|(_, account)| matches_all(account, program_id, filters))
+                .map(|(key, account)| (*key, account.clone()))
+                .collect(),
+        ))
+    }
+}
+
+/// One account per `<pubkey>.json` file, in the format printed by
+/// `solana account <pubkey> --output json`, plus the slot it was read at when recorded
+#[derive(Serialize, Deserialize)]
+pub struct AccountFixture {
+    pub pubkey: String,
+    pub account: UiAccount,
+    #[serde(default)]
+    pub slot: u64,
+}
+
+impl AccountFixture {
+    pub fn new(key: &Pubkey, account: &Account, slot: u64) -> Self {
+        AccountFixture {
+            pubkey: key.to_string(),
+            account: encode_ui_account(key, account, UiAccountEncoding::Base64, None, None),
+            slot,
+        }
+    }
+
+    pub fn decode(&self) -> Result<(Pubkey, Account)> {
+        let key = self.pubkey.parse::<Pubkey>()?;
+        let account = self
+            .account
+            .decode::<Account>()
+            .ok_or_else(|| anyhow!("Failed to decode fixture account {}", key))?;
+        Ok((key, account))
+    }
+}
+
+/// Reads accounts from a directory of JSON fixtures, each file being looked up on demand
+pub struct FixtureAccountSource {
+    pub dir: PathBuf,
+}
+
+impl FixtureAccountSource {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        FixtureAccountSource {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn fixture_path(&self, key: &Pubkey) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    async fn read_fixture(path: &Path) -> Result<AccountFixture> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read fixture {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse fixture {}", path.display()))
+    }
+
+    async fn find_account(&self, key: &Pubkey) -> Result<Option<(u64, Account)>> {
+        let path = self.fixture_path(key);
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+
+        let fixture = Self::read_fixture(&path).await?;
+        let (_, account) = fixture.decode()?;
+        Ok(Some((fixture.slot, account)))
+    }
+}
+
+/// Reads report the latest slot among the fixtures they return
+#[async_trait]
+impl AccountSource for FixtureAccountSource {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let found = try_join_all(keys.iter().map(|key| self.find_account(key))).await?;
+
+        let slot = found.iter().flatten().map(|(slot, _)| *slot).max();
+        let accounts = found
+            .into_iter()
+            .map(|found| found.map(|(_, account)| account))
+            .collect();
+
+        Ok((slot.unwrap_or_default(), accounts))
+    }
+
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let mut entries = tokio::fs::read_dir(&self.dir)
+            .await
+            .with_context(|| format!("Failed to read fixture dir {}", self.dir.display()))?;
+
+        let mut slot = 0;
+        let mut accounts = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let fixture = Self::read_fixture(&path).await?;
+            let (key, account) = fixture.decode()?;
+            if matches_all(&account, program_id, filters) {
+                slot = slot.max(fixture.slot);
+                accounts.push((key, account));
+            }
+        }
+
+        Ok((slot, accounts))
+    }
+}
+
+/// Passes reads through to `inner` and saves every account it returns as a fixture in `dir`,
+/// so that `FixtureAccountSource` can replay them. Later reads of an account overwrite earlier
+/// ones, unless they were made at an older slot.
+pub struct RecordingAccountSource {
+    pub inner: Arc<dyn AccountSource>,
+    pub fixtures: FixtureAccountSource,
+    /// Slot each account was last recorded at. Held across the write, so that concurrent reads
+    /// of an account cannot leave the older state on disk.
+    recorded_slots: Mutex<HashMap<Pubkey, u64>>,
+}
+
+impl RecordingAccountSource {
+    pub fn new(inner: Arc<dyn AccountSource>, dir: impl AsRef<Path>) -> Self {
+        RecordingAccountSource {
+            inner,
+            fixtures: FixtureAccountSource::new(dir),
+            recorded_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Written to a temporary file first and renamed over the fixture, so a replay never reads
+    /// a half written file. A failed write is logged rather than failing the read that triggered it.
+    async fn record(&self, key: &Pubkey, account: &Account, slot: u64) {
+        let mut recorded_slots = self.recorded_slots.lock().await;
+        if recorded_slots
+            .get(key)
+            .is_some_and(|recorded| *recorded > slot)
+        {
+            return;
+        }
+
+        let path = self.fixtures.fixture_path(key);
+        let temp_path = path.with_extension("json.tmp");
+        let result = async {
+            let content = serde_json::to_string_pretty(&AccountFixture::new(key, account, slot))?;
+            tokio::fs::create_dir_all(&self.fixtures.dir).await?;
+            tokio::fs::write(&temp_path, content).await?;
+            tokio::fs::rename(&temp_path, &path).await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                recorded_slots.insert(*key, slot);
+            }
+            Err(e) => warn!("⚠️ Failed to record account {:?}: {}", key, e),
+        }
+    }
+}
+
+#[async_trait]
+impl AccountSource for RecordingAccountSource {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let (slot, accounts) = self.inner.get_multiple_accounts_with_slot(keys).await?;
+        for (key, account) in keys.iter().zip(&accounts) {
+            if let Some(account) = account {
+                self.record(key, account, slot).await;
+            }
+        }
+
+        Ok((slot, accounts))
+    }
+
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let (slot, accounts) = self
+            .inner
+            .get_program_accounts_with_slot(program_id, filters)
+            .await?;
+        for (key, account) in &accounts {
+            self.record(key, account, slot).await;
+        }
+
+        Ok((slot, accounts))
+    }
+
+    async fn get_token_accounts_by_owner_with_slot(
+        &self,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        let (slot, accounts) = self
+            .inner
+            .get_token_accounts_by_owner_with_slot(owner, token_program)
+            .await?;
+        for (key, account) in &accounts {
+            self.record(key, account, slot).await;
+        }
+
+        Ok((slot, accounts))
+    }
+
+    fn is_live(&self) -> bool {
+        self.inner.is_live()
+    }
+
+    async fn observe(&self, key: &Pubkey, account: &Account, slot: u64) {
+        self.record(key, account, slot).await;
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use saros_sdk::{state::bin_array::BinArray, utils::helper::get_pair_bin_array};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+use crate::{error::AppError, state::AccountSource};
+
+/// Number of bins stored in a single `BinArray` account
+pub const BIN_ARRAY_SIZE: u32 = 256;
+
+/// Upper bound on the number of bins returned by a single range query
+pub const MAX_BIN_RANGE: u32 = 2048;
+
+#[derive(Clone, Debug)]
+pub struct BinLiquidity {
+    pub id: u32,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+pub fn bin_array_index_of(bin_id: u32) -> u32 {
+    bin_id / BIN_ARRAY_SIZE
+}
+
+pub fn bin_array_address(pair_key: &Pubkey, program_id: &Pubkey, index: u32) -> Pubkey {
+    get_pair_bin_array(index, pair_key, program_id).0
+}
+
+/// Fetches the bin arrays with the given indexes, skipping the ones that are not initialized
+pub async fn fetch_bin_arrays(
+    source: Arc<dyn AccountSource>,
+    pair_key: Pubkey,
+    program_id: Pubkey,
+    indexes: &[u32],
+) -> Result<HashMap<u32, BinArray>> {
+    let keys: Vec<Pubkey> = indexes
+        .iter()
+        .map(|index| bin_array_address(&pair_key, &program_id, *index))
+        .collect();
+
+    let accounts = source
+        .get_multiple_accounts(&keys)
+        .await
+        .context("Failed to get bin array accounts")?;
+
+    let mut bin_arrays = HashMap::new();
+    for (index, account) in indexes.iter().zip(accounts) {
+        if let Some(account) = account {
+            let bin_array = BinArray::unpack(&account.data).map_err(|e| {
+                AppError::DecodeFailure(format!("bin array {} of pair {}: {}", index, pair_key, e))
+            })?;
+            bin_arrays.insert(*index, bin_array);
+        }
+    }
+
+    Ok(bin_arrays)
+}
+
+/// Lists the reserves of every bin in `from..=to`, bins in missing arrays are reported empty
+pub fn collect_bins(bin_arrays: &HashMap<u32, BinArray>, from: u32, to: u32) -> Vec<BinLiquidity> {
+    (from..=to)
+        .map(|id| {
+            let bin = bin_arrays
+                .get(&bin_array_index_of(id))
+                .map(|bin_array| &bin_array.bins[(id % BIN_ARRAY_SIZE) as usize]);
+
+            BinLiquidity {
+                id,
+                reserve_x: bin.map_or(0, |bin| bin.reserve_x),
+                reserve_y: bin.map_or(0, |bin| bin.reserve_y),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::{app::DLMM_PROGRAM_ID, state::MemoryAccountSource};
+
+    #[tokio::test]
+    async fn truncated_bin_array_is_decode_failure() {
+        let pair_key = Pubkey::new_unique();
+        let source = MemoryAccountSource::default();
+        source.insert(
+            bin_array_address(&pair_key, &DLMM_PROGRAM_ID, 0),
+            Account {
+                lamports: 1,
+                data: vec![0u8; 64],
+                owner: DLMM_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let Err(error) = fetch_bin_arrays(Arc::new(source), pair_key, DLMM_PROGRAM_ID, &[0]).await
+        else {
+            panic!("a truncated bin array was decoded");
+        };
+        assert_eq!(AppError::from(error).code(), "decode_failure");
+    }
+}
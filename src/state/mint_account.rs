@@ -1,8 +1,11 @@
 use std::sync::Arc;
 
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::state::AccountSource;
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct MintAccount {
     pub key: Pubkey,
@@ -10,14 +13,15 @@ pub struct MintAccount {
 }
 
 impl MintAccount {
-    pub fn fetch(client: Arc<RpcClient>, mint_key: Pubkey) -> Self {
-        let account = client
+    pub async fn fetch(source: Arc<dyn AccountSource>, mint_key: Pubkey) -> Result<Self> {
+        let account = source
             .get_account(&mint_key)
-            .expect("Failed to get mint account");
+            .await
+            .with_context(|| format!("Failed to get mint account {}", mint_key))?;
 
-        MintAccount {
+        Ok(MintAccount {
             key: mint_key,
             account,
-        }
+        })
     }
 }
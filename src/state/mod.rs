@@ -1,18 +1,32 @@
+mod account_source;
+mod bin_range;
 mod mint_account;
 mod pair_account;
+mod pair_market;
+mod pair_registry;
 mod pool_state;
+mod position;
+mod rewarder;
 mod token_meta;
 mod types;
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
+pub use account_source::*;
 use anyhow::Result;
+pub use bin_range::*;
 use jupiter_amm_interface::KeyedAccount;
 pub use mint_account::*;
+pub use pair_market::*;
+pub use pair_registry::*;
 pub use pool_state::*;
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
+pub use position::*;
+pub use rewarder::*;
+use saros_sdk::state::{bin_array::BinArray, pair::Pair};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
 pub use token_meta::*;
+
+use crate::error::AppError;
 pub use types::*;
 
 pub struct State {
@@ -21,59 +35,157 @@ pub struct State {
 }
 
 pub trait Fetch {
-    fn fetch(client: Arc<RpcClient>, key: Pubkey) -> Result<Self>
+    async fn fetch(source: Arc<dyn AccountSource>, key: Pubkey) -> Result<Self>
     where
         Self: Sized;
 }
 
 impl Fetch for KeyedAccount {
-    fn fetch(client: Arc<RpcClient>, pair_key: Pubkey) -> Result<Self> {
-        pair_account::fetch(client, pair_key)
+    async fn fetch(source: Arc<dyn AccountSource>, pair_key: Pubkey) -> Result<Self> {
+        pair_account::fetch(source, pair_key).await
     }
 }
 
 impl State {
-    pub async fn generate_state_async(client: Arc<RpcClient>, pair_account: KeyedAccount) -> Self {
-        tokio::task::spawn_blocking(move || {
-            // ---- run in a separate thread, safe with runtime ----
-            let state = PoolState::fetch(client.clone(), pair_account);
-            let mint_x_account = MintAccount::fetch(client.clone(), state.mint_x);
-            let mint_y_account = MintAccount::fetch(client.clone(), state.mint_y);
-
-            State {
-                pool_state: Some(state),
-                mint_accounts: vec![mint_x_account, mint_y_account],
-            }
+    /// Loads the pool state and both mints, all at once since they only depend on the pair
+    pub async fn generate_state_async(
+        source: Arc<dyn AccountSource>,
+        pair_account: KeyedAccount,
+    ) -> Result<Self> {
+        let pair = Pair::unpack(&pair_account.account.data)
+            .map_err(|e| AppError::DecodeFailure(format!("pair {}: {}", pair_account.key, e)))?;
+
+        let (pool_state, mint_x_account, mint_y_account) = tokio::try_join!(
+            PoolState::fetch(source.clone(), pair_account),
+            MintAccount::fetch(source.clone(), pair.token_mint_x),
+            MintAccount::fetch(source.clone(), pair.token_mint_y)
+        )?;
+
+        Ok(State {
+            pool_state,
+            mint_accounts: vec![mint_x_account, mint_y_account],
         })
-        .await
-        .expect("spawn_blocking failed")
+    }
+
+    pub async fn generate_mint_account(
+        source: Arc<dyn AccountSource>,
+        mint_key: Pubkey,
+    ) -> Result<MintAccount> {
+        MintAccount::fetch(source, mint_key).await
     }
 
     pub async fn generate_keyed_account(
-        client: Arc<RpcClient>,
+        source: Arc<dyn AccountSource>,
         pair_key: Pubkey,
     ) -> Result<KeyedAccount> {
-        let pair_account = tokio::task::spawn_blocking(move || {
-            // ---- run in a separate thread, safe with runtime ----
-            KeyedAccount::fetch(client.clone(), pair_key)
-        })
-        .await
-        .expect("spawn_blocking failed")?;
-
-        Ok(pair_account)
+        KeyedAccount::fetch(source, pair_key).await
     }
 
     pub async fn generate_token_state(
-        client: Arc<RpcClient>,
+        source: Arc<dyn AccountSource>,
         mint_key: Pubkey,
     ) -> Result<TokenMeta> {
-        let token_state = tokio::task::spawn_blocking(move || {
-            // ---- run in a separate thread, safe with runtime ----
-            TokenMeta::fetch(client.clone(), mint_key)
-        })
+        TokenMeta::fetch(source, mint_key).await
+    }
+
+    pub async fn generate_program_pairs(
+        source: Arc<dyn AccountSource>,
+        program_id: Pubkey,
+    ) -> Result<Vec<PairSummary>> {
+        fetch_program_pairs(source, program_id).await
+    }
+
+    pub async fn generate_pair_markets(
+        source: Arc<dyn AccountSource>,
+        pairs: Vec<PairSummary>,
+        token_programs: HashMap<Pubkey, Pubkey>,
+    ) -> Result<Vec<PairMarket>> {
+        fetch_pair_markets(source, &pairs, &token_programs).await
+    }
+
+    pub async fn generate_owner_positions(
+        source: Arc<dyn AccountSource>,
+        owner: Pubkey,
+        program_id: Pubkey,
+    ) -> Result<Vec<Position>> {
+        fetch_owner_positions(
+            source,
+            owner,
+            program_id,
+            &[spl_token::ID, spl_token_2022::ID],
+        )
         .await
-        .expect("spawn_blocking failed")?;
+    }
+
+    pub async fn generate_position(
+        source: Arc<dyn AccountSource>,
+        address: Pubkey,
+        program_id: Pubkey,
+    ) -> Result<Position> {
+        fetch_position(source, address, program_id).await
+    }
+
+    pub async fn generate_rewarder_hook(
+        source: Arc<dyn AccountSource>,
+        address: Pubkey,
+        pair: Pubkey,
+    ) -> Result<RewarderHook> {
+        fetch_rewarder_hook(source, address, pair).await
+    }
+
+    pub async fn generate_pair_hooks(
+        source: Arc<dyn AccountSource>,
+        pairs: &[Pubkey],
+        program_id: Pubkey,
+    ) -> Result<HashMap<Pubkey, RewarderHook>> {
+        fetch_pair_hooks(source, pairs, program_id).await
+    }
+
+    pub async fn generate_pending_rewards(
+        source: Arc<dyn AccountSource>,
+        hook: &RewarderHook,
+        position: &Position,
+    ) -> Result<u64> {
+        fetch_pending_rewards(source, hook, position).await
+    }
+
+    pub async fn generate_bin_arrays(
+        source: Arc<dyn AccountSource>,
+        pair_key: Pubkey,
+        program_id: Pubkey,
+        indexes: Vec<u32>,
+    ) -> Result<HashMap<u32, BinArray>> {
+        fetch_bin_arrays(source, pair_key, program_id, &indexes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::app::DLMM_PROGRAM_ID;
+
+    #[tokio::test]
+    async fn truncated_pair_is_decode_failure() {
+        let pair_account = KeyedAccount {
+            key: Pubkey::new_unique(),
+            account: Account {
+                lamports: 1,
+                data: vec![0u8; 16],
+                owner: DLMM_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+            params: None,
+        };
 
-        Ok(token_state)
+        let Err(error) =
+            State::generate_state_async(Arc::new(MemoryAccountSource::default()), pair_account)
+                .await
+        else {
+            panic!("a truncated pair was decoded");
+        };
+        assert_eq!(AppError::from(error).code(), "decode_failure");
     }
 }
@@ -3,11 +3,12 @@ use std::sync::Arc;
 use jupiter_amm_interface::{KeyedAccount, KeyedUiAccount};
 use serde::{Deserialize, Serialize};
 use solana_account_decoder::{encode_ui_account, UiAccountEncoding};
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::{account::Account, pubkey::Pubkey};
 
 use anyhow::Result;
 
+use crate::{error::AppError, state::AccountSource};
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct PairAccount {
     pub key: Pubkey,
@@ -16,8 +17,8 @@ pub struct PairAccount {
 }
 
 impl PairAccount {
-    pub fn fetch(client: Arc<RpcClient>, pair_key: Pubkey) -> Result<PairAccount> {
-        let account = client.get_account(&pair_key)?;
+    pub async fn fetch(source: Arc<dyn AccountSource>, pair_key: Pubkey) -> Result<PairAccount> {
+        let account = get_pair_account(source, pair_key).await?;
 
         let ui_account =
             encode_ui_account(&pair_key, &account, UiAccountEncoding::Base64, None, None);
@@ -46,8 +47,21 @@ impl PairAccount {
     }
 }
 
-pub fn fetch(client: Arc<RpcClient>, pair_key: Pubkey) -> Result<KeyedAccount> {
-    let account = client.get_account(&pair_key)?;
+/// Fails with `AppError::PairNotFound` when there is no account at `pair_key`
+async fn get_pair_account(source: Arc<dyn AccountSource>, pair_key: Pubkey) -> Result<Account> {
+    let account = source
+        .get_multiple_accounts(&[pair_key])
+        .await?
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or(AppError::PairNotFound(pair_key))?;
+
+    Ok(account)
+}
+
+pub async fn fetch(source: Arc<dyn AccountSource>, pair_key: Pubkey) -> Result<KeyedAccount> {
+    let account = get_pair_account(source, pair_key).await?;
 
     Ok(KeyedAccount {
         key: pair_key,
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use saros_sdk::state::pair::Pair;
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+
+use crate::{
+    dlmm::token_account_amount,
+    error::AppError,
+    state::{AccountSource, PairSummary},
+};
+
+/// Live pair state along with the raw balances of its two vaults
+pub struct PairMarket {
+    pub address: Pubkey,
+    pub pair: Pair,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    /// Slot the pair and its vaults were read at
+    pub slot: u64,
+}
+
+/// The pair holds its reserves in its own associated token accounts
+pub fn pair_vault_address(pair_key: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(pair_key, mint, token_program)
+}
+
+/// Fetches each pair with both of its vaults, `token_programs` maps every mint to its owner.
+/// Pairs that no longer exist are skipped.
+pub async fn fetch_pair_markets(
+    source: Arc<dyn AccountSource>,
+    pairs: &[PairSummary],
+    token_programs: &HashMap<Pubkey, Pubkey>,
+) -> Result<Vec<PairMarket>> {
+    let mut keys = Vec::with_capacity(pairs.len() * 3);
+    for summary in pairs {
+        let token_program_x = token_programs
+            .get(&summary.token_mint_x)
+            .context("Missing token program for mint X")?;
+        let token_program_y = token_programs
+            .get(&summary.token_mint_y)
+            .context("Missing token program for mint Y")?;
+
+        keys.push(summary.address);
+        keys.push(pair_vault_address(
+            &summary.address,
+            &summary.token_mint_x,
+            token_program_x,
+        ));
+        keys.push(pair_vault_address(
+            &summary.address,
+            &summary.token_mint_y,
+            token_program_y,
+        ));
+    }
+
+    let (slot, accounts) = source
+        .get_multiple_accounts_with_slot(&keys)
+        .await
+        .context("Failed to get pair and vault accounts")?;
+
+    let mut markets = vec![];
+    for (summary, accounts) in pairs.iter().zip(accounts.chunks(3)) {
+        let [pair_account, vault_x, vault_y] = accounts else {
+            continue;
+        };
+        let Some(pair_account) = pair_account else {
+            continue;
+        };
+
+        let pair = Pair::unpack(&pair_account.data)
+            .map_err(|e| AppError::DecodeFailure(format!("pair {}: {}", summary.address, e)))?;
+        let reserve_of = |vault: &Option<Account>| {
+            vault
+                .as_ref()
+                .and_then(|vault| token_account_amount(&vault.data))
+                .unwrap_or_default()
+        };
+
+        markets.push(PairMarket {
+            address: summary.address,
+            pair,
+            reserve_x: reserve_of(vault_x),
+            reserve_y: reserve_of(vault_y),
+            slot,
+        });
+    }
+
+    Ok(markets)
+}
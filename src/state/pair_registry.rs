@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use saros_sdk::{state::pair::Pair, utils::helper::is_swap_for_y};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::{
+    state::{AccountSource, DataFilter},
+    utils::anchor_discriminator,
+};
+
+/// Upper bound on the page size of a registry query
+pub const MAX_PAIRS_PAGE: usize = 500;
+
+#[derive(Clone, Debug)]
+pub struct PairSummary {
+    pub address: Pubkey,
+    pub token_mint_x: Pubkey,
+    pub token_mint_y: Pubkey,
+    pub bin_step: u8,
+    pub active_id: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairSort {
+    #[default]
+    BinStep,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PairQuery {
+    /// Only pairs trading this mint, on either side
+    pub mint: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub sort: PairSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PairListing {
+    pub address: String,
+    pub token_mint_x: String,
+    pub token_mint_y: String,
+    pub bin_step: u8,
+    pub active_id: u32,
+}
+
+impl From<&PairSummary> for PairListing {
+    fn from(summary: &PairSummary) -> Self {
+        PairListing {
+            address: summary.address.to_string(),
+            token_mint_x: summary.token_mint_x.to_string(),
+            token_mint_y: summary.token_mint_y.to_string(),
+            bin_step: summary.bin_step,
+            active_id: summary.active_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PairPage {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub pairs: Vec<PairListing>,
+}
+
+/// Every pair owned by the DLMM program, as of the last scan
+#[derive(Default)]
+pub struct PairRegistry {
+    pub pairs: Vec<PairSummary>,
+    pub last_refreshed: Option<Instant>,
+}
+
+impl PairRegistry {
+    pub fn query(&self, mint: Option<Pubkey>, query: &PairQuery) -> PairPage {
+        let mut pairs: Vec<&PairSummary> = self
+            .pairs
+            .iter()
+            .filter(|pair| {
+                mint.is_none_or(|mint| pair.token_mint_x == mint || pair.token_mint_y == mint)
+            })
+            .collect();
+
+        match query.sort {
+            PairSort::BinStep => pairs.sort_by_key(|pair| (pair.bin_step, pair.address)),
+        }
+        if query.order == SortOrder::Desc {
+            pairs.reverse();
+        }
+
+        let limit = query.limit.unwrap_or(100).min(MAX_PAIRS_PAGE);
+        PairPage {
+            total: pairs.len(),
+            offset: query.offset,
+            limit,
+            pairs: pairs
+                .into_iter()
+                .skip(query.offset)
+                .take(limit)
+                .map(PairListing::from)
+                .collect(),
+        }
+    }
+
+    /// Pairs trading `mint_a` against `mint_b`, whichever of the two is token X
+    pub fn find_by_mints(&self, mint_a: Pubkey, mint_b: Pubkey) -> Vec<PairSummary> {
+        self.pairs
+            .iter()
+            .filter(|pair| {
+                let (source, destination) = if is_swap_for_y(mint_a, pair.token_mint_x) {
+                    (pair.token_mint_x, pair.token_mint_y)
+                } else {
+                    (pair.token_mint_y, pair.token_mint_x)
+                };
+                source == mint_a && destination == mint_b
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Scans the program for every `Pair` account, skipping the ones that fail to decode
+pub async fn fetch_program_pairs(
+    source: Arc<dyn AccountSource>,
+    program_id: Pubkey,
+) -> Result<Vec<PairSummary>> {
+    let accounts = source
+        .get_program_accounts(
+            &program_id,
+            &[DataFilter::new(0, &anchor_discriminator("account:Pair"))],
+        )
+        .await
+        .context("Failed to scan DLMM program accounts")?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(address, account)| match Pair::unpack(&account.data) {
+            Ok(pair) => Some(PairSummary {
+                address,
+                token_mint_x: pair.token_mint_x,
+                token_mint_y: pair.token_mint_y,
+                bin_step: pair.bin_step,
+                active_id: pair.active_id,
+            }),
+            Err(e) => {
+                warn!("⚠️ Skipping undecodable pair {:?}: {}", address, e);
+                None
+            }
+        })
+        .collect())
+}
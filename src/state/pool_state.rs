@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
+use anyhow::{Context, Result};
 use jupiter_amm_interface::KeyedAccount;
 use saros_sdk::state::bin_array::BinArray;
 use saros_sdk::state::pair::Pair;
 use saros_sdk::utils::helper::get_pair_bin_array;
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+
+use crate::{error::AppError, state::AccountSource};
 
 pub struct PoolState {
     pub mint_x: Pubkey,
@@ -29,9 +31,13 @@ impl PoolState {
         }
     }
 
-    pub fn fetch(client: Arc<RpcClient>, pair_account: KeyedAccount) -> Self {
-        let pair_state =
-            Pair::unpack(&pair_account.account.data).expect("Failed to unpack pair account");
+    /// Loads the two bin arrays around the active bin, `None` when one of them is not initialized
+    pub async fn fetch(
+        source: Arc<dyn AccountSource>,
+        pair_account: KeyedAccount,
+    ) -> Result<Option<Self>> {
+        let pair_state = Pair::unpack(&pair_account.account.data)
+            .map_err(|e| AppError::DecodeFailure(format!("pair {}: {}", pair_account.key, e)))?;
         let bin_array_index = pair_state.bin_array_index();
         let (bin_array_lower_key, bin_array_upper_key) = get_pair_bin_array(
             bin_array_index,
@@ -39,22 +45,27 @@ impl PoolState {
             &pair_account.account.owner,
         );
 
-        let bin_array_lower_account = client
-            .get_account(&bin_array_lower_key)
-            .expect("Failed to get bin array lower account");
-        let bin_array_lower = BinArray::unpack(&bin_array_lower_account.data)
-            .expect("Failed to unpack bin array lower account");
-        let bin_array_upper_account = client
-            .get_account(&bin_array_upper_key)
-            .expect("Failed to get bin array upper account");
-        let bin_array_upper = BinArray::unpack(&bin_array_upper_account.data)
-            .expect("Failed to unpack bin array upper account");
-
-        PoolState::new(
+        let accounts = source
+            .get_multiple_accounts(&[bin_array_lower_key, bin_array_upper_key])
+            .await
+            .context("Failed to get active bin arrays")?;
+        let [Some(bin_array_lower_account), Some(bin_array_upper_account)] = accounts.as_slice()
+        else {
+            return Ok(None);
+        };
+
+        let unpack_bin_array = |key: &Pubkey, account: &Account| {
+            BinArray::unpack(&account.data)
+                .map_err(|e| AppError::DecodeFailure(format!("bin array {}: {}", key, e)))
+        };
+        let bin_array_lower = unpack_bin_array(&bin_array_lower_key, bin_array_lower_account)?;
+        let bin_array_upper = unpack_bin_array(&bin_array_upper_key, bin_array_upper_account)?;
+
+        Ok(Some(PoolState::new(
             pair_state.token_mint_x,
             pair_state.token_mint_y,
             bin_array_lower,
             bin_array_upper,
-        )
+        )))
     }
 }
@@ -0,0 +1,393 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use futures_util::future::try_join_all;
+use saros_sdk::state::bin_array::BinArray;
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+use crate::{
+    dlmm::token_account_amount,
+    error::AppError,
+    state::{bin_array_index_of, AccountSource, BIN_ARRAY_SIZE},
+    utils::anchor_discriminator,
+};
+
+/// Number of bins a single position spans at most
+pub const POSITION_MAX_BINS: usize = 64;
+
+/// Offsets in the account data, after the 8 bytes Anchor discriminator
+const PAIR_OFFSET: usize = 8;
+const POSITION_MINT_OFFSET: usize = PAIR_OFFSET + 32;
+const LIQUIDITY_SHARES_OFFSET: usize = POSITION_MINT_OFFSET + 32;
+const LOWER_BIN_ID_OFFSET: usize = LIQUIDITY_SHARES_OFFSET + 16 * POSITION_MAX_BINS;
+const UPPER_BIN_ID_OFFSET: usize = LOWER_BIN_ID_OFFSET + 4;
+const POSITION_LEN: usize = UPPER_BIN_ID_OFFSET + 4;
+
+/// A liquidity position. Ownership goes with the position NFT `position_mint`, the position
+/// account itself is a PDA of the program.
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub address: Pubkey,
+    pub pair: Pubkey,
+    pub position_mint: Pubkey,
+    /// Shares of each bin from `lower_bin_id`, the ones past `upper_bin_id` are unused
+    pub liquidity_shares: [u128; POSITION_MAX_BINS],
+    pub lower_bin_id: u32,
+    pub upper_bin_id: u32,
+}
+
+impl Position {
+    /// Decodes the account data: discriminator, pair, position mint, the liquidity shares
+    /// and the bin range
+    pub fn unpack(address: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < POSITION_LEN
+            || data[..PAIR_OFFSET] != anchor_discriminator("account:Position")
+        {
+            return Err(AppError::DecodeFailure(format!("position {}", address)).into());
+        }
+
+        let pair = Pubkey::try_from(&data[PAIR_OFFSET..POSITION_MINT_OFFSET])?;
+        let position_mint = Pubkey::try_from(&data[POSITION_MINT_OFFSET..LIQUIDITY_SHARES_OFFSET])?;
+        let mut liquidity_shares = [0u128; POSITION_MAX_BINS];
+        for (i, share) in liquidity_shares.iter_mut().enumerate() {
+            let start = LIQUIDITY_SHARES_OFFSET + 16 * i;
+            *share = u128::from_le_bytes(data[start..start + 16].try_into()?);
+        }
+        let lower_bin_id =
+            u32::from_le_bytes(data[LOWER_BIN_ID_OFFSET..UPPER_BIN_ID_OFFSET].try_into()?);
+        let upper_bin_id = u32::from_le_bytes(data[UPPER_BIN_ID_OFFSET..POSITION_LEN].try_into()?);
+
+        if upper_bin_id < lower_bin_id
+            || (upper_bin_id - lower_bin_id) as usize >= POSITION_MAX_BINS
+        {
+            return Err(AppError::DecodeFailure(format!(
+                "position {}: invalid bin range {}..={}",
+                address, lower_bin_id, upper_bin_id
+            ))
+            .into());
+        }
+
+        Ok(Position {
+            address,
+            pair,
+            position_mint,
+            liquidity_shares,
+            lower_bin_id,
+            upper_bin_id,
+        })
+    }
+
+    /// `(bin id, liquidity share)` of every bin in the range
+    pub fn bin_shares(&self) -> impl Iterator<Item = (u32, u128)> + '_ {
+        (self.lower_bin_id..=self.upper_bin_id).zip(self.liquidity_shares.iter().copied())
+    }
+
+    /// Indexes of the bin arrays holding the position's bins
+    pub fn bin_array_indexes(&self) -> Vec<u32> {
+        (bin_array_index_of(self.lower_bin_id)..=bin_array_index_of(self.upper_bin_id)).collect()
+    }
+}
+
+/// The tokens a position can withdraw from one bin
+#[derive(Clone, Debug)]
+pub struct PositionBinAmounts {
+    pub bin_id: u32,
+    pub liquidity_share: u128,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Splits each bin's reserves pro rata to the position's share of the bin supply.
+/// Bins whose array is not loaded are reported empty.
+pub fn position_bin_amounts(
+    position: &Position,
+    bin_arrays: &HashMap<u32, BinArray>,
+) -> Vec<PositionBinAmounts> {
+    position
+        .bin_shares()
+        .map(|(bin_id, liquidity_share)| {
+            let bin = bin_arrays
+                .get(&bin_array_index_of(bin_id))
+                .map(|bin_array| &bin_array.bins[(bin_id % BIN_ARRAY_SIZE) as usize]);
+
+            let (amount_x, amount_y) = match bin {
+                Some(bin) if bin.total_supply > 0 => (
+                    share_of(bin.reserve_x, liquidity_share, bin.total_supply),
+                    share_of(bin.reserve_y, liquidity_share, bin.total_supply),
+                ),
+                _ => (0, 0),
+            };
+
+            PositionBinAmounts {
+                bin_id,
+                liquidity_share,
+                amount_x,
+                amount_y,
+            }
+        })
+        .collect()
+}
+
+/// `amount * share / supply`, rounded down like the program does on withdrawal
+pub fn share_of(amount: u64, share: u128, supply: u128) -> u64 {
+    match share.checked_mul(amount as u128) {
+        Some(product) => (product / supply) as u64,
+        None => (amount as f64 * (share as f64 / supply as f64)) as u64,
+    }
+}
+
+pub fn position_address(position_mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"position", position_mint.as_ref()], program_id).0
+}
+
+/// Loads a single position, failing when `address` is not a position of `program_id`
+pub async fn fetch_position(
+    source: Arc<dyn AccountSource>,
+    address: Pubkey,
+    program_id: Pubkey,
+) -> Result<Position> {
+    let account = source
+        .get_multiple_accounts(&[address])
+        .await
+        .context("Failed to get position account")?
+        .into_iter()
+        .next()
+        .flatten()
+        .filter(|account| account.owner == program_id)
+        .ok_or_else(|| AppError::InvalidInput(format!("{} is not a DLMM position", address)))?;
+
+    Position::unpack(address, &account.data)
+}
+
+/// Mints of the NFTs held by `owner` under any of `token_programs`: token accounts holding
+/// exactly one token
+async fn fetch_owned_nft_mints(
+    source: Arc<dyn AccountSource>,
+    owner: Pubkey,
+    token_programs: &[Pubkey],
+) -> Result<Vec<Pubkey>> {
+    let token_accounts = try_join_all(
+        token_programs
+            .iter()
+            .map(|token_program| source.get_token_accounts_by_owner(&owner, token_program)),
+    )
+    .await
+    .context("Failed to get token accounts of owner")?;
+
+    Ok(token_accounts
+        .into_iter()
+        .flatten()
+        .filter(|(_, account)| token_account_amount(&account.data) == Some(1))
+        .filter_map(|(_, account)| Pubkey::try_from(account.data.get(..32)?).ok())
+        .collect())
+}
+
+/// Every position of `program_id` whose NFT is held by `owner`, skipping the ones that fail
+/// to decode
+pub async fn fetch_owner_positions(
+    source: Arc<dyn AccountSource>,
+    owner: Pubkey,
+    program_id: Pubkey,
+    token_programs: &[Pubkey],
+) -> Result<Vec<Position>> {
+    let mints = fetch_owned_nft_mints(source.clone(), owner, token_programs).await?;
+    let addresses: Vec<Pubkey> = mints
+        .iter()
+        .map(|mint| position_address(mint, &program_id))
+        .collect();
+
+    let accounts = source
+        .get_multiple_accounts(&addresses)
+        .await
+        .context("Failed to get position accounts")?;
+
+    // Most NFTs in a wallet are not positions, their PDA simply does not exist
+    Ok(addresses
+        .into_iter()
+        .zip(accounts)
+        .filter_map(|(address, account)| {
+            let account = account.filter(|account| account.owner == program_id)?;
+            match Position::unpack(address, &account.data) {
+                Ok(position) => Some(position),
+                Err(e) => {
+                    warn!("⚠️ Skipping undecodable position {:?}: {}", address, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::{account::Account, signer::Signer};
+
+    use super::*;
+    use crate::{
+        app::DLMM_PROGRAM_ID,
+        dlmm::build_create_position_transaction,
+        state::{LiquidityShape, MemoryAccountSource, PositionRange},
+        test_support::{token_account, TestBank},
+    };
+
+    /// Position data laid out field by field like the program's `Position` account
+    fn position_data(
+        pair: &Pubkey,
+        position_mint: &Pubkey,
+        shares: &[u128],
+        lower_bin_id: u32,
+        upper_bin_id: u32,
+    ) -> Vec<u8> {
+        let mut data = anchor_discriminator("account:Position").to_vec();
+        data.extend_from_slice(pair.as_ref());
+        data.extend_from_slice(position_mint.as_ref());
+        for i in 0..POSITION_MAX_BINS {
+            data.extend_from_slice(&shares.get(i).copied().unwrap_or_default().to_le_bytes());
+        }
+        data.extend_from_slice(&lower_bin_id.to_le_bytes());
+        data.extend_from_slice(&upper_bin_id.to_le_bytes());
+        data
+    }
+
+    /// Code of the error decoding `data` fails with
+    fn decode_error(data: &[u8]) -> &'static str {
+        let Err(error) = Position::unpack(Pubkey::new_unique(), data) else {
+            panic!("invalid position data was decoded");
+        };
+        AppError::from(error).code()
+    }
+
+    #[test]
+    fn decodes_every_field_at_its_offset() {
+        let (pair, position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let shares = [7, 0, u128::MAX, 1 << 64];
+        let mut data = position_data(&pair, &position_mint, &shares, 8_388_606, 8_388_609);
+        // Fields the program appends after the bin range are ignored
+        data.extend_from_slice(&[0xff; 64]);
+
+        let position = Position::unpack(Pubkey::new_unique(), &data).unwrap();
+        assert_eq!(position.pair, pair);
+        assert_eq!(position.position_mint, position_mint);
+        assert_eq!(
+            position.bin_shares().collect::<Vec<_>>(),
+            vec![
+                (8_388_606, 7),
+                (8_388_607, 0),
+                (8_388_608, u128::MAX),
+                (8_388_609, 1 << 64)
+            ]
+        );
+    }
+
+    #[test]
+    fn inverted_bin_range_is_decode_failure() {
+        let data = position_data(&Pubkey::new_unique(), &Pubkey::new_unique(), &[], 10, 9);
+        assert_eq!(decode_error(&data), "decode_failure");
+    }
+
+    #[tokio::test]
+    async fn owner_positions_skip_undecodable_accounts() {
+        let owner = Pubkey::new_unique();
+        let program_id = DLMM_PROGRAM_ID;
+        let (good_mint, bad_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pair = Pubkey::new_unique();
+
+        let source = MemoryAccountSource::default();
+        for mint in [good_mint, bad_mint] {
+            source.insert(
+                Pubkey::new_unique(),
+                token_account(&mint, &owner, 1, &spl_token::ID),
+            );
+        }
+        let account = |data| Account {
+            lamports: 1,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        };
+        source.insert(
+            position_address(&good_mint, &program_id),
+            account(position_data(&pair, &good_mint, &[1], 100, 101)),
+        );
+        source.insert(
+            position_address(&bad_mint, &program_id),
+            account(vec![0u8; 32]),
+        );
+
+        let positions =
+            fetch_owner_positions(Arc::new(source), owner, program_id, &[spl_token::ID])
+                .await
+                .unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].position_mint, good_mint);
+    }
+
+    #[tokio::test]
+    async fn decodes_a_position_written_by_the_program() -> Result<()> {
+        let Some(mut bank) = TestBank::start(1_000_000_000).await? else {
+            return Ok(());
+        };
+        let ctx = bank.app_context().await?;
+        let active_id = bank.pair.active_id;
+
+        let built = build_create_position_transaction(
+            &ctx,
+            bank.pair.address,
+            bank.user.pubkey(),
+            PositionRange::Bins {
+                lower: active_id - 2,
+                upper: active_id + 2,
+            },
+            1_000_000,
+            1_000_000,
+            LiquidityShape::Spot,
+        )
+        .await?;
+        bank.sign_and_process(&built.transaction).await?;
+
+        let address = Pubkey::from_str(&built.position)?;
+        let account = bank
+            .context
+            .banks_client
+            .get_account(address)
+            .await?
+            .context("The position was not created")?;
+        let position = Position::unpack(address, &account.data)?;
+
+        assert_eq!(position.pair, bank.pair.address);
+        assert_eq!(position.position_mint.to_string(), built.position_mint);
+        assert_eq!(
+            (position.lower_bin_id, position.upper_bin_id),
+            (active_id - 2, active_id + 2)
+        );
+        // Shares sit exactly in the bins the deposit went to
+        for (bin_id, share) in position.bin_shares() {
+            let deposited = built
+                .bins
+                .iter()
+                .any(|bin| bin.bin_id == bin_id && (bin.amount_x > 0 || bin.amount_y > 0));
+            assert_eq!(share > 0, deposited, "bin {}", bin_id);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn garbage_data_is_decode_failure() {
+        assert_eq!(decode_error(&[]), "decode_failure");
+        assert_eq!(decode_error(&[0xab; POSITION_LEN]), "decode_failure");
+    }
+
+    #[test]
+    fn truncated_data_is_decode_failure() {
+        let mut data = vec![0u8; POSITION_LEN];
+        data[..PAIR_OFFSET].copy_from_slice(&anchor_discriminator("account:Position"));
+
+        assert_eq!(decode_error(&data[..POSITION_LEN - 1]), "decode_failure");
+        assert_eq!(decode_error(&data[..PAIR_OFFSET]), "decode_failure");
+    }
+}
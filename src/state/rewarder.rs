@@ -0,0 +1,518 @@
+//! Farm rewards are paid by a rewarder hook program a pair can attach (`Pair::hook`). Its
+//! accounts are decoded by hand like positions are, and every offset the interface relies on
+//! lives in this file. A hook is only used once it points back at its pair, the layout is
+//! checked against a recorded hook by the tests, and claims are simulated before being handed out.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use saros_sdk::state::pair::Pair;
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use tracing::warn;
+
+use crate::{
+    error::AppError,
+    state::{bin_array_index_of, AccountSource, Position, BIN_ARRAY_SIZE, POSITION_MAX_BINS},
+    utils::anchor_discriminator,
+};
+
+/// Rewards per share are fixed point numbers with this many fractional bits
+pub const REWARD_PER_SHARE_BITS: u32 = 64;
+
+/// Offsets in the hook account data, after the 8 bytes Anchor discriminator
+const HOOK_AUTHORITY_OFFSET: usize = 8;
+const HOOK_PAIR_OFFSET: usize = HOOK_AUTHORITY_OFFSET + 32;
+const HOOK_REWARD_MINT_OFFSET: usize = HOOK_PAIR_OFFSET + 32;
+const HOOK_RESERVE_OFFSET: usize = HOOK_REWARD_MINT_OFFSET + 32;
+const HOOK_LEN: usize = HOOK_RESERVE_OFFSET + 32;
+
+/// Offsets in a hook bin array: the hook, the array index and the reward per share of each bin
+const HOOK_BIN_ARRAY_INDEX_OFFSET: usize = 8 + 32;
+const HOOK_BINS_OFFSET: usize = HOOK_BIN_ARRAY_INDEX_OFFSET + 4;
+const HOOK_BIN_ARRAY_LEN: usize = HOOK_BINS_OFFSET + 16 * BIN_ARRAY_SIZE as usize;
+
+/// Offsets in a hook position: the reward per share of each bin when the position last
+/// settled, then the rewards settled but not claimed yet
+const HOOK_POSITION_SNAPSHOTS_OFFSET: usize = 8;
+const HOOK_POSITION_PENDING_OFFSET: usize = HOOK_POSITION_SNAPSHOTS_OFFSET + 16 * POSITION_MAX_BINS;
+const HOOK_POSITION_LEN: usize = HOOK_POSITION_PENDING_OFFSET + 8;
+
+/// The rewarder attached to a pair, owned by the hook program
+#[derive(Clone, Debug)]
+pub struct RewarderHook {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub pair: Pubkey,
+    pub reward_token_mint: Pubkey,
+    /// Token account the rewards are paid from
+    pub reward_reserve: Pubkey,
+}
+
+impl RewarderHook {
+    pub fn unpack(address: Pubkey, program_id: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < HOOK_LEN
+            || data[..HOOK_AUTHORITY_OFFSET] != anchor_discriminator("account:Hook")
+        {
+            return Err(AppError::DecodeFailure(format!("rewarder hook {}", address)).into());
+        }
+
+        Ok(RewarderHook {
+            address,
+            program_id,
+            pair: Pubkey::try_from(&data[HOOK_PAIR_OFFSET..HOOK_REWARD_MINT_OFFSET])?,
+            reward_token_mint: Pubkey::try_from(
+                &data[HOOK_REWARD_MINT_OFFSET..HOOK_RESERVE_OFFSET],
+            )?,
+            reward_reserve: Pubkey::try_from(&data[HOOK_RESERVE_OFFSET..HOOK_LEN])?,
+        })
+    }
+
+    /// The hook's copy of a bin array, tracking the rewards per share of its bins
+    pub fn bin_array_address(&self, index: u32) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"bin_array", self.address.as_ref(), &index.to_le_bytes()],
+            &self.program_id,
+        )
+        .0
+    }
+
+    /// The hook's record of a DLMM position, created when liquidity first goes in
+    pub fn position_address(&self, position: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"position", self.address.as_ref(), position.as_ref()],
+            &self.program_id,
+        )
+        .0
+    }
+}
+
+/// Rewards per share accumulated by each bin of an array since the hook started
+#[derive(Clone, Debug)]
+pub struct HookBinArray {
+    pub index: u32,
+    pub rewards_per_share: Vec<u128>,
+}
+
+impl HookBinArray {
+    pub fn unpack(address: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < HOOK_BIN_ARRAY_LEN || data[..8] != anchor_discriminator("account:BinArray")
+        {
+            return Err(AppError::DecodeFailure(format!("hook bin array {}", address)).into());
+        }
+
+        let index =
+            u32::from_le_bytes(data[HOOK_BIN_ARRAY_INDEX_OFFSET..HOOK_BINS_OFFSET].try_into()?);
+        let rewards_per_share = data[HOOK_BINS_OFFSET..HOOK_BIN_ARRAY_LEN]
+            .chunks_exact(16)
+            .map(|bin| u128::from_le_bytes(bin.try_into().unwrap_or_default()))
+            .collect();
+
+        Ok(HookBinArray {
+            index,
+            rewards_per_share,
+        })
+    }
+}
+
+/// Where a position stands with the hook
+#[derive(Clone, Debug)]
+pub struct HookPosition {
+    /// Rewards per share of each bin from the position's lower bin when it last settled
+    pub rewards_per_share: [u128; POSITION_MAX_BINS],
+    /// Rewards settled but not claimed yet
+    pub pending_rewards: u64,
+}
+
+impl HookPosition {
+    pub fn unpack(address: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < HOOK_POSITION_LEN
+            || data[..HOOK_POSITION_SNAPSHOTS_OFFSET]
+                != anchor_discriminator("account:UserPosition")
+        {
+            return Err(AppError::DecodeFailure(format!("hook position {}", address)).into());
+        }
+
+        let mut rewards_per_share = [0u128; POSITION_MAX_BINS];
+        for (i, snapshot) in rewards_per_share.iter_mut().enumerate() {
+            let start = HOOK_POSITION_SNAPSHOTS_OFFSET + 16 * i;
+            *snapshot = u128::from_le_bytes(data[start..start + 16].try_into()?);
+        }
+        let pending_rewards =
+            u64::from_le_bytes(data[HOOK_POSITION_PENDING_OFFSET..HOOK_POSITION_LEN].try_into()?);
+
+        Ok(HookPosition {
+            rewards_per_share,
+            pending_rewards,
+        })
+    }
+}
+
+/// `a * b >> 64` without overflowing, saturating when the result does not fit
+fn mul_shr_64(a: u128, b: u128) -> u128 {
+    let (a_hi, a_lo) = (a >> 64, a & u64::MAX as u128);
+    let (b_hi, b_lo) = (b >> 64, b & u64::MAX as u128);
+
+    (a_hi * b_hi)
+        .saturating_mul(1 << 64)
+        .saturating_add(a_hi * b_lo)
+        .saturating_add(a_lo * b_hi)
+        .saturating_add((a_lo * b_lo) >> REWARD_PER_SHARE_BITS)
+}
+
+/// Rewards the position can claim as of the hook's last update: what was settled, plus each
+/// bin's share of the rewards per share accumulated since. Bins whose hook array is not loaded
+/// add nothing, and a position the hook never saw has earned nothing.
+pub fn pending_rewards(
+    position: &Position,
+    hook_position: Option<&HookPosition>,
+    hook_bin_arrays: &HashMap<u32, HookBinArray>,
+) -> u64 {
+    let Some(hook_position) = hook_position else {
+        return 0;
+    };
+
+    let accrued = position
+        .bin_shares()
+        .zip(hook_position.rewards_per_share)
+        .filter_map(|((bin_id, share), snapshot)| {
+            let rewards_per_share = hook_bin_arrays
+                .get(&bin_array_index_of(bin_id))?
+                .rewards_per_share
+                .get((bin_id % BIN_ARRAY_SIZE) as usize)?;
+            Some(mul_shr_64(
+                share,
+                rewards_per_share.saturating_sub(snapshot),
+            ))
+        })
+        .fold(0u128, u128::saturating_add);
+
+    u64::try_from(accrued)
+        .unwrap_or(u64::MAX)
+        .saturating_add(hook_position.pending_rewards)
+}
+
+/// Loads the rewarder hook `address` of `pair`, its program is the owner of the account
+pub async fn fetch_rewarder_hook(
+    source: Arc<dyn AccountSource>,
+    address: Pubkey,
+    pair: Pubkey,
+) -> Result<RewarderHook> {
+    let account = source
+        .get_multiple_accounts(&[address])
+        .await
+        .context("Failed to get rewarder hook account")?
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or_else(|| AppError::DecodeFailure(format!("rewarder hook {} not found", address)))?;
+
+    let hook = RewarderHook::unpack(address, account.owner, &account.data)?;
+    if hook.pair != pair {
+        return Err(AppError::DecodeFailure(format!(
+            "rewarder hook {} belongs to pair {}, not {}",
+            address, hook.pair, pair
+        ))
+        .into());
+    }
+    Ok(hook)
+}
+
+/// The rewarder hooks of the pairs, keyed by pair. Pairs without hook are left out, and so are
+/// pairs and hooks that fail to decode or hooks that do not point back at their pair.
+pub async fn fetch_pair_hooks(
+    source: Arc<dyn AccountSource>,
+    pairs: &[Pubkey],
+    program_id: Pubkey,
+) -> Result<HashMap<Pubkey, RewarderHook>> {
+    let pair_accounts = source
+        .get_multiple_accounts(pairs)
+        .await
+        .context("Failed to get pair accounts")?;
+    let pair_hooks: Vec<(Pubkey, Pubkey)> = pairs
+        .iter()
+        .zip(pair_accounts)
+        .filter_map(|(pair, account)| {
+            let account = account.filter(|account| account.owner == program_id)?;
+            match Pair::unpack(&account.data) {
+                Ok(decoded) => Some((*pair, decoded.hook?)),
+                Err(e) => {
+                    warn!("⚠️ Skipping undecodable pair {:?}: {}", pair, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let hook_keys: Vec<Pubkey> = pair_hooks.iter().map(|(_, hook)| *hook).collect();
+    let hook_accounts = source
+        .get_multiple_accounts(&hook_keys)
+        .await
+        .context("Failed to get rewarder hook accounts")?;
+
+    Ok(pair_hooks
+        .into_iter()
+        .zip(hook_accounts)
+        .filter_map(|((pair, address), account)| {
+            let Some(account) = account else {
+                warn!(
+                    "⚠️ Skipping missing rewarder hook {:?} of pair {:?}",
+                    address, pair
+                );
+                return None;
+            };
+            match RewarderHook::unpack(address, account.owner, &account.data) {
+                Ok(hook) if hook.pair == pair => Some((pair, hook)),
+                Ok(hook) => {
+                    warn!(
+                        "⚠️ Skipping rewarder hook {:?}, it belongs to pair {:?} and not {:?}",
+                        address, hook.pair, pair
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("⚠️ Skipping undecodable rewarder hook {:?}: {}", address, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// Reads the hook's records of the position and of its bins, and sums what it can claim
+pub async fn fetch_pending_rewards(
+    source: Arc<dyn AccountSource>,
+    hook: &RewarderHook,
+    position: &Position,
+) -> Result<u64> {
+    let indexes = position.bin_array_indexes();
+    let hook_position_address = hook.position_address(&position.address);
+    let mut keys = vec![hook_position_address];
+    keys.extend(indexes.iter().map(|index| hook.bin_array_address(*index)));
+
+    let mut accounts = source
+        .get_multiple_accounts(&keys)
+        .await
+        .context("Failed to get hook accounts of position")?
+        .into_iter();
+
+    let hook_position = accounts
+        .next()
+        .flatten()
+        .map(|account| HookPosition::unpack(hook_position_address, &account.data))
+        .transpose()?;
+    let mut hook_bin_arrays = HashMap::new();
+    for (key, account) in keys[1..].iter().zip(accounts) {
+        if let Some(account) = account {
+            let bin_array = HookBinArray::unpack(*key, &account.data)?;
+            hook_bin_arrays.insert(bin_array.index, bin_array);
+        }
+    }
+
+    Ok(pending_rewards(
+        position,
+        hook_position.as_ref(),
+        &hook_bin_arrays,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::DLMM_PROGRAM_ID,
+        state::{fetch_program_pairs, MemoryAccountSource},
+        test_support::{account, pair_account, read_fixtures, HOOK_PAIR_FIXTURES_DIR},
+    };
+
+    /// Far enough from the edges of its bin array for small positions to stay within it
+    const LOWER_BIN_ID: u32 = 8_388_618;
+
+    fn position(shares: &[u128]) -> Position {
+        let mut liquidity_shares = [0u128; POSITION_MAX_BINS];
+        liquidity_shares[..shares.len()].copy_from_slice(shares);
+        Position {
+            address: Pubkey::new_unique(),
+            pair: Pubkey::new_unique(),
+            position_mint: Pubkey::new_unique(),
+            liquidity_shares,
+            lower_bin_id: LOWER_BIN_ID,
+            upper_bin_id: LOWER_BIN_ID + shares.len() as u32 - 1,
+        }
+    }
+
+    fn hook_position(snapshots: &[u128], pending_rewards: u64) -> HookPosition {
+        let mut rewards_per_share = [0u128; POSITION_MAX_BINS];
+        rewards_per_share[..snapshots.len()].copy_from_slice(snapshots);
+        HookPosition {
+            rewards_per_share,
+            pending_rewards,
+        }
+    }
+
+    /// A hook bin array where every bin accumulated `reward_per_share`
+    fn hook_bin_arrays(index: u32, reward_per_share: u128) -> HashMap<u32, HookBinArray> {
+        HashMap::from([(
+            index,
+            HookBinArray {
+                index,
+                rewards_per_share: vec![reward_per_share; BIN_ARRAY_SIZE as usize],
+            },
+        )])
+    }
+
+    #[test]
+    fn decodes_the_hook_fields_at_their_offsets() {
+        let (pair, mint, reserve) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut data = anchor_discriminator("account:Hook").to_vec();
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(pair.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(reserve.as_ref());
+        data.extend_from_slice(&[0xff; 32]);
+
+        let hook = RewarderHook::unpack(Pubkey::new_unique(), Pubkey::new_unique(), &data).unwrap();
+        assert_eq!(hook.pair, pair);
+        assert_eq!(hook.reward_token_mint, mint);
+        assert_eq!(hook.reward_reserve, reserve);
+
+        let error = RewarderHook::unpack(Pubkey::new_unique(), Pubkey::new_unique(), &data[..100])
+            .unwrap_err();
+        assert_eq!(AppError::from(error).code(), "decode_failure");
+    }
+
+    #[test]
+    fn decodes_the_hook_position_fields_at_their_offsets() {
+        let mut data = anchor_discriminator("account:UserPosition").to_vec();
+        for i in 0..POSITION_MAX_BINS as u128 {
+            data.extend_from_slice(&(i << 64).to_le_bytes());
+        }
+        data.extend_from_slice(&42u64.to_le_bytes());
+
+        let hook_position = HookPosition::unpack(Pubkey::new_unique(), &data).unwrap();
+        assert_eq!(hook_position.rewards_per_share[3], 3 << 64);
+        assert_eq!(hook_position.pending_rewards, 42);
+
+        let error =
+            HookPosition::unpack(Pubkey::new_unique(), &data[..data.len() - 1]).unwrap_err();
+        assert_eq!(AppError::from(error).code(), "decode_failure");
+    }
+
+    #[test]
+    fn mul_shr_64_keeps_the_high_bits() {
+        assert_eq!(mul_shr_64(1 << 64, 5), 5);
+        assert_eq!(mul_shr_64(3, 1 << 63), 1);
+        assert_eq!(mul_shr_64(1 << 100, 1 << 20), 1 << 56);
+        assert_eq!(mul_shr_64(1 << 100, 1 << 100), u128::MAX);
+        assert_eq!(mul_shr_64(u128::MAX, u128::MAX), u128::MAX);
+    }
+
+    #[test]
+    fn pending_rewards_add_each_bin_since_its_snapshot() {
+        let index = bin_array_index_of(LOWER_BIN_ID);
+        let position = position(&[1 << 64, 2 << 64, 0]);
+        // The first bin settled at 4, so only 6 of the 10 per share are still owed
+        let hook_position = hook_position(&[4, 0, 0], 7);
+
+        assert_eq!(
+            pending_rewards(&position, Some(&hook_position), &hook_bin_arrays(index, 10)),
+            6 + 2 * 10 + 7
+        );
+    }
+
+    #[test]
+    fn pending_rewards_skip_what_is_not_loaded() {
+        let position = position(&[1 << 64]);
+        let arrays = hook_bin_arrays(bin_array_index_of(LOWER_BIN_ID), 10);
+
+        assert_eq!(pending_rewards(&position, None, &arrays), 0);
+        assert_eq!(
+            pending_rewards(&position, Some(&hook_position(&[], 3)), &HashMap::new()),
+            3
+        );
+    }
+
+    /// Hook account data laid out like `decodes_the_hook_fields_at_their_offsets` expects
+    fn hook_data(pair: &Pubkey) -> Vec<u8> {
+        let mut data = anchor_discriminator("account:Hook").to_vec();
+        for key in [
+            Pubkey::new_unique(),
+            *pair,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ] {
+            data.extend_from_slice(key.as_ref());
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn pair_hooks_skip_what_does_not_decode_or_belongs_elsewhere() -> Result<()> {
+        let hook_program = Pubkey::new_unique();
+        let pairs: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let hooks: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+
+        let source = MemoryAccountSource::default();
+        for (pair, hook) in pairs.iter().zip(&hooks) {
+            source.insert(*pair, pair_account(|decoded| decoded.hook = Some(*hook)));
+        }
+        // A pair without hook, then a hook that is garbage, one of another pair and one missing
+        source.insert(pairs[1], pair_account(|_| {}));
+        source.insert(hooks[0], account(hook_data(&pairs[0]), hook_program));
+        source.insert(hooks[2], account(vec![0xab; 200], hook_program));
+        source.insert(hooks[3], account(hook_data(&pairs[0]), hook_program));
+
+        let found = fetch_pair_hooks(Arc::new(source), &pairs, DLMM_PROGRAM_ID).await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[&pairs[0]].address, hooks[0]);
+        assert_eq!(found[&pairs[0]].program_id, hook_program);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decodes_a_recorded_rewarder_hook() -> Result<()> {
+        let Some(fixtures) = read_fixtures(HOOK_PAIR_FIXTURES_DIR)? else {
+            return Ok(());
+        };
+        let source: Arc<dyn AccountSource> =
+            Arc::new(MemoryAccountSource::new(fixtures.into_iter().collect()));
+        let pair = fetch_program_pairs(source.clone(), DLMM_PROGRAM_ID)
+            .await?
+            .into_iter()
+            .next()
+            .context("The fixtures hold no pair")?;
+
+        let hooks = fetch_pair_hooks(source.clone(), &[pair.address], DLMM_PROGRAM_ID).await?;
+        let hook = hooks
+            .get(&pair.address)
+            .context("The recorded hook does not decode as the hook of its pair")?;
+
+        // The reserve is a token account of the reward mint
+        let index = bin_array_index_of(pair.active_id);
+        let bin_array_key = hook.bin_array_address(index);
+        let [reserve, bin_array] = source
+            .get_multiple_accounts(&[hook.reward_reserve, bin_array_key])
+            .await?
+            .try_into()
+            .expect("two keys were asked for");
+        let reserve = reserve.context("The reward reserve was not recorded")?;
+        assert_eq!(
+            Pubkey::try_from(&reserve.data[..32])?,
+            hook.reward_token_mint
+        );
+
+        // The hook keeps the active bin in an array at the address its seeds give
+        let bin_array = bin_array.context("No hook bin array at the derived address")?;
+        assert_eq!(bin_array.owner, hook.program_id);
+        assert_eq!(
+            HookBinArray::unpack(bin_array_key, &bin_array.data)?.index,
+            index
+        );
+
+        Ok(())
+    }
+}
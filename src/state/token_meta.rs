@@ -2,7 +2,6 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use mpl_token_metadata::accounts::Metadata;
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
 use spl_token_2022_interface::{
     extension::{BaseStateWithExtensions, StateWithExtensions},
@@ -12,6 +11,8 @@ use spl_token_2022_interface::{
 use spl_token_metadata_interface::state::TokenMetadata;
 use tracing::info;
 
+use crate::{error::AppError, state::AccountSource};
+
 #[derive(Clone)]
 pub struct TokenMeta {
     pub mint: Pubkey,
@@ -30,12 +31,13 @@ impl Default for TokenMeta {
 }
 
 impl TokenMeta {
-    pub fn fetch(client: Arc<RpcClient>, mint_key: Pubkey) -> Result<TokenMeta> {
-        let token_account = client.get_account(&mint_key)?;
+    pub async fn fetch(source: Arc<dyn AccountSource>, mint_key: Pubkey) -> Result<TokenMeta> {
+        let token_account = source.get_account(&mint_key).await?;
         match token_account.owner {
             spl_token::ID => {
-                let mint_account = spl_token::state::Mint::unpack(&token_account.data)?;
-                let token_meta = Self::get_spl_token_metadata(client, &mint_key)?;
+                let mint_account = spl_token::state::Mint::unpack(&token_account.data)
+                    .map_err(|e| AppError::DecodeFailure(format!("mint {}: {}", mint_key, e)))?;
+                let token_meta = Self::get_spl_token_metadata(source, &mint_key).await?;
                 return Ok(TokenMeta {
                     mint: mint_key,
                     decimals: mint_account.decimals,
@@ -44,7 +46,8 @@ impl TokenMeta {
                 });
             }
             spl_token_2022::ID => {
-                let mint_state = StateWithExtensions::<Mint2022>::unpack(&token_account.data)?;
+                let mint_state = StateWithExtensions::<Mint2022>::unpack(&token_account.data)
+                    .map_err(|e| AppError::DecodeFailure(format!("mint {}: {}", mint_key, e)))?;
                 // Get all extension types enabled on this mint
                 // let extension_types = mint_state.get_extension_types()?;
                 // info!("\nExtensions enabled: {:?}", extension_types);
@@ -71,7 +74,10 @@ impl TokenMeta {
         }
     }
 
-    fn get_spl_token_metadata(client: Arc<RpcClient>, mint_key: &Pubkey) -> Result<Metadata> {
+    async fn get_spl_token_metadata(
+        source: Arc<dyn AccountSource>,
+        mint_key: &Pubkey,
+    ) -> Result<Metadata> {
         let (metadata_pda, _) = Pubkey::find_program_address(
             &[
                 b"metadata",
@@ -81,9 +87,11 @@ impl TokenMeta {
             &mpl_token_metadata::ID,
         );
 
-        let account = client.get_account_data(&metadata_pda)?;
+        let account = source.get_account(&metadata_pda).await?.data;
 
-        let metadata = Metadata::safe_deserialize(&mut &account[..])?;
+        let metadata = Metadata::safe_deserialize(&mut &account[..]).map_err(|e| {
+            AppError::DecodeFailure(format!("metadata of mint {}: {}", mint_key, e))
+        })?;
         let symbol = String::from_utf8_lossy(metadata.symbol.as_bytes())
             .trim_end_matches('\0')
             .to_string();
@@ -108,3 +116,34 @@ impl TokenMeta {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::state::MemoryAccountSource;
+
+    #[tokio::test]
+    async fn truncated_mint_is_decode_failure() {
+        for token_program in [spl_token::ID, spl_token_2022::ID] {
+            let mint_key = Pubkey::new_unique();
+            let source = MemoryAccountSource::default();
+            source.insert(
+                mint_key,
+                Account {
+                    lamports: 1,
+                    data: vec![0u8; 20],
+                    owner: token_program,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+
+            let Err(error) = TokenMeta::fetch(Arc::new(source), mint_key).await else {
+                panic!("a truncated mint was decoded");
+            };
+            assert_eq!(AppError::from(error).code(), "decode_failure");
+        }
+    }
+}
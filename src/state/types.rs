@@ -1,6 +1,9 @@
+use jupiter_amm_interface::SwapMode;
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 
+use crate::{dlmm::SwapPath, error::AppError};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Status {
     #[serde(rename = "ok")]
@@ -17,20 +20,60 @@ pub struct WebJsonResponse {
     pub message: String,
     pub data: serde_json::Value,
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QuoteResponse {
+    pub source_mint: String,
+    pub destination_mint: String,
+    pub swap_mode: QuoteSwapMode,
+    /// `input` or `output`, the side fixed by the requested amount
+    pub fixed_side: String,
     pub in_amount: u64,
     pub out_amount: u64,
     pub fee_amount: u64,
-    pub fee_mint: Pubkey,
-}
-impl Default for QuoteResponse {
-    fn default() -> Self {
-        QuoteResponse {
-            in_amount: 0,
-            out_amount: 0,
-            fee_amount: 0,
-            fee_mint: Pubkey::default(),
+    pub fee_mint: String,
+    pub in_amount_ui: f64,
+    pub out_amount_ui: f64,
+    pub fee_amount_ui: f64,
+    pub slippage_bps: u16,
+    /// Output guaranteed after slippage, equal to `out_amount` for exact-out
+    pub min_out_amount: u64,
+    /// Input spent at most after slippage, equal to `in_amount` for exact-in
+    pub max_in_amount: u64,
+    /// Destination per source token at the active bin, in UI units
+    pub spot_price: f64,
+    /// Destination per source token actually received, fees included
+    pub execution_price: f64,
+    pub price_impact_bps: f64,
+    /// Bin by bin replay of the swap, only filled for verbose quotes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<SwapPath>,
+}
+
+/// Which side of the swap the quoted amount fixes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteSwapMode {
+    /// `amount` is the exact input, the output is quoted
+    #[default]
+    ExactIn,
+    /// `amount` is the exact output, the input is quoted
+    ExactOut,
+}
+
+impl QuoteSwapMode {
+    pub fn fixed_side(&self) -> &'static str {
+        match self {
+            QuoteSwapMode::ExactIn => "input",
+            QuoteSwapMode::ExactOut => "output",
+        }
+    }
+}
+
+impl From<QuoteSwapMode> for SwapMode {
+    fn from(mode: QuoteSwapMode) -> Self {
+        match mode {
+            QuoteSwapMode::ExactIn => SwapMode::ExactIn,
+            QuoteSwapMode::ExactOut => SwapMode::ExactOut,
         }
     }
 }
@@ -40,12 +83,297 @@ pub struct QuoteRequest {
     pub pair_address: String,
     pub source_mint: String,
     pub destination_mint: String,
+    /// Input amount for `exact_in`, output amount for `exact_out`
+    #[serde(alias = "amount_in")]
+    pub amount: u64,
+    #[serde(default)]
+    pub swap_mode: QuoteSwapMode,
+    #[serde(default = "default_slippage_bps")]
+    pub slippage_bps: u16,
+    /// Also replay the swap across the bins and report each bin crossed
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 50;
+
+fn default_slippage_bps() -> u16 {
+    DEFAULT_SLIPPAGE_BPS
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SimulateSwapRequest {
+    pub pair_address: String,
+    pub user: String,
+    pub source_mint: String,
     pub amount_in: u64,
 }
 
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapDirection {
+    /// Sell token X for token Y
+    XToY,
+    /// Sell token Y for token X
+    YToX,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BuildSwapRequest {
+    pub pair_address: String,
+    pub user: String,
+    pub direction: SwapDirection,
+    pub amount_in: u64,
+    pub slippage_bps: u16,
+}
+
+/// Message sent by a WebSocket client to start (or replace) its live quote
+#[derive(Deserialize, Debug)]
+pub struct QuoteSubscription {
+    pub pair: String,
+    pub source_mint: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub swap_mode: QuoteSwapMode,
+    #[serde(default = "default_slippage_bps")]
+    pub slippage_bps: u16,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub symbol: String,
     pub mint: Pubkey,
     pub decimals: u8,
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BinResponse {
+    pub id: u32,
+    pub price: f64,
+    pub reserve_x: f64,
+    pub reserve_y: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairBinsResponse {
+    pub pair_address: String,
+    pub token_mint_x: String,
+    pub token_mint_y: String,
+    pub symbol_x: String,
+    pub symbol_y: String,
+    pub bin_step: u8,
+    pub active_id: u32,
+    pub bins: Vec<BinResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairMarketResponse {
+    pub pair_address: String,
+    pub token_mint_x: String,
+    pub token_mint_y: String,
+    pub symbol_x: String,
+    pub symbol_y: String,
+    pub bin_step: u8,
+    pub active_id: u32,
+    /// Price of one X in Y at the active bin, adjusted for decimals
+    pub price: f64,
+    /// Vault balances in UI units
+    pub reserve_x: f64,
+    pub reserve_y: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionBinResponse {
+    pub bin_id: u32,
+    pub price: f64,
+    /// Share of the bin supply, as a string since it overflows JSON numbers
+    pub liquidity_share: String,
+    pub amount_x: f64,
+    pub amount_y: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionResponse {
+    pub position_address: String,
+    pub position_mint: String,
+    pub pair_address: String,
+    pub token_mint_x: String,
+    pub token_mint_y: String,
+    pub symbol_x: String,
+    pub symbol_y: String,
+    pub lower_bin_id: u32,
+    pub upper_bin_id: u32,
+    pub active_id: u32,
+    /// Whether the active bin lies within the position's range, i.e. it earns fees
+    pub in_range: bool,
+    /// Price of one X in Y at the active bin, adjusted for decimals
+    pub price: f64,
+    /// Tokens the position would withdraw right now, in UI units. Saros compounds swap fees
+    /// into the bin reserves, so earned fees are part of these and not reported apart.
+    pub amount_x: f64,
+    pub amount_y: f64,
+    /// Farm rewards waiting to be claimed, only for pairs with a rewarder hook. Not part of
+    /// `value`, the reward token usually trades on another pair.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_reward: Option<PendingReward>,
+    /// Amounts valued in Y, the quote token, at the active bin price
+    pub value: f64,
+    /// Only reported when an entry snapshot is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pnl: Option<PositionPnl>,
+    /// Bins the position holds shares of
+    pub bins: Vec<PositionBinResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingReward {
+    pub reward_mint: String,
+    pub symbol: String,
+    /// Raw amount, as claimed
+    pub raw_amount: u64,
+    /// The same amount in UI units
+    pub amount: f64,
+}
+
+/// What was deposited into a position, in UI units, and the price of X in Y at the time
+#[derive(Debug, Clone, Copy)]
+pub struct PositionEntry {
+    pub amount_x: f64,
+    pub amount_y: f64,
+    pub price: f64,
+}
+
+impl PositionEntry {
+    /// The snapshot is either given in full or not at all
+    pub fn from_parts(
+        amount_x: Option<f64>,
+        amount_y: Option<f64>,
+        price: Option<f64>,
+    ) -> Result<Option<Self>, AppError> {
+        match (amount_x, amount_y, price) {
+            (Some(amount_x), Some(amount_y), Some(price)) => Ok(Some(PositionEntry {
+                amount_x,
+                amount_y,
+                price,
+            })),
+            (None, None, None) => Ok(None),
+            _ => Err(AppError::InvalidInput(
+                "An entry snapshot needs the amounts of X and Y and the entry price".to_string(),
+            )),
+        }
+    }
+}
+
+/// Performance of a position against its entry, every value in Y
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionPnl {
+    /// The deposit valued at the entry price
+    pub entry_value: f64,
+    /// The deposit had it been held instead, valued at the current price
+    pub hold_value: f64,
+    /// Current value minus `entry_value`
+    pub pnl: f64,
+    /// Current value minus `hold_value`, i.e. fees earned net of impermanent loss
+    pub pnl_vs_hold: f64,
+    pub pnl_vs_hold_pct: f64,
+}
+
+impl PositionPnl {
+    pub fn new(entry: &PositionEntry, price: f64, value: f64) -> Self {
+        let entry_value = entry.amount_x * entry.price + entry.amount_y;
+        let hold_value = entry.amount_x * price + entry.amount_y;
+        let pnl_vs_hold = value - hold_value;
+
+        PositionPnl {
+            entry_value,
+            hold_value,
+            pnl: value - entry_value,
+            pnl_vs_hold,
+            pnl_vs_hold_pct: if hold_value > 0.0 {
+                pnl_vs_hold / hold_value * 100.0
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// How a deposit is spread across the bins of a position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidityShape {
+    /// The same weight in every bin
+    #[default]
+    Spot,
+    /// Concentrated around the active bin
+    Curve,
+    /// Growing away from the active bin
+    BidAsk,
+}
+
+/// Bins of a position, given as bin ids or as prices of X in Y adjusted for decimals
+#[derive(Debug, Clone, Copy)]
+pub enum PositionRange {
+    Bins { lower: u32, upper: u32 },
+    Prices { min: f64, max: f64 },
+}
+
+impl PositionRange {
+    pub fn from_parts(
+        lower_bin_id: Option<u32>,
+        upper_bin_id: Option<u32>,
+        min_price: Option<f64>,
+        max_price: Option<f64>,
+    ) -> Result<Self, AppError> {
+        match (lower_bin_id, upper_bin_id, min_price, max_price) {
+            (Some(lower), Some(upper), None, None) => Ok(PositionRange::Bins { lower, upper }),
+            (None, None, Some(min), Some(max)) => Ok(PositionRange::Prices { min, max }),
+            _ => Err(AppError::InvalidInput(
+                "Give either `lower_bin_id` and `upper_bin_id`, or `min_price` and `max_price`"
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreatePositionRequest {
+    pub pair_address: String,
+    pub user: String,
+    pub lower_bin_id: Option<u32>,
+    pub upper_bin_id: Option<u32>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    /// Raw amounts deposited, X goes to the bins from the active one up, Y from the active one down
+    pub amount_x: u64,
+    pub amount_y: u64,
+    #[serde(default)]
+    pub shape: LiquidityShape,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AddLiquidityRequest {
+    pub position_address: String,
+    pub user: String,
+    /// Raw amounts deposited over the position's bins
+    pub amount_x: u64,
+    pub amount_y: u64,
+    #[serde(default)]
+    pub shape: LiquidityShape,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemoveLiquidityRequest {
+    pub position_address: String,
+    pub user: String,
+    /// Part of the liquidity withdrawn from each chosen bin, 10000 empties them
+    pub bps: u16,
+    /// Bins to withdraw from, every bin of the position when omitted
+    pub bin_ids: Option<Vec<u32>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClaimRewardsRequest {
+    /// Wallet holding the positions
+    pub user: String,
+}
@@ -0,0 +1,354 @@
+//! Apps reading accounts from memory, with pairs and bin arrays built in place, and the Saros
+//! program running on a `solana-program-test` bank loaded with the accounts of a pair. Pairs are
+//! recorded with `just fixtures <PAIR> <HOOK_PAIR>`, tests using recorded fixtures pass without
+//! running while these are missing, unless `REQUIRE_PROGRAM_FIXTURES` is set as `just test` does.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use saros_sdk::state::{bin_array::BinArray, pair::Pair};
+use serde_json::json;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_program::program_option::COption;
+use solana_program_test::{BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account, clock::Clock, native_token::LAMPORTS_PER_SOL, program_pack::Pack,
+    pubkey::Pubkey, rent::Rent, signature::Keypair, signer::Signer, system_program, sysvar,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+use spl_token::{
+    native_mint,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+
+use crate::{
+    app::{AppConfig, AppContext, DLMM_PROGRAM_ID},
+    dlmm::token_account_amount,
+    state::{
+        fetch_program_pairs, AccountFixture, AccountSource, DataFilter, MemoryAccountSource,
+        PairSummary, BIN_ARRAY_SIZE,
+    },
+    utils::anchor_discriminator,
+};
+
+/// Accounts of the pair under test, as written by `pair record`
+pub const PAIR_FIXTURES_DIR: &str = "tests/fixtures/pair";
+
+/// Accounts of a pair with a rewarder hook and of the hook, as written by `pair record`
+pub const HOOK_PAIR_FIXTURES_DIR: &str = "tests/fixtures/hook_pair";
+
+/// The Saros program dumped from mainnet, where `ProgramTest` looks for `saros_dlmm`
+pub const PROGRAM_FIXTURE: &str = "tests/fixtures/saros_dlmm.so";
+
+/// Set to fail the tests using fixtures instead of skipping them when fixtures are missing
+pub const REQUIRE_FIXTURES_VAR: &str = "REQUIRE_PROGRAM_FIXTURES";
+
+/// Whether every fixture path exists. A test missing one is skipped with a warning, or fails
+/// when `REQUIRE_PROGRAM_FIXTURES` is set.
+fn fixtures_present(paths: &[&str]) -> Result<bool> {
+    let Some(missing) = paths.iter().find(|path| !Path::new(path).exists()) else {
+        return Ok(true);
+    };
+    if std::env::var_os(REQUIRE_FIXTURES_VAR).is_some() {
+        bail!("{} is missing, run `just fixtures` first", missing);
+    }
+    eprintln!(
+        "⚠️ Skipping a fixture test, {} is missing. Run `just fixtures` to record it.",
+        missing
+    );
+    Ok(false)
+}
+
+pub fn account(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: 1,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// An app reading `accounts` from memory, along with a clock
+pub fn memory_context(accounts: impl IntoIterator<Item = (Pubkey, Account)>) -> Arc<AppContext> {
+    let source = MemoryAccountSource::default();
+    source.insert(
+        sysvar::clock::ID,
+        account(bincode::serialize(&Clock::default()).unwrap(), sysvar::ID),
+    );
+    for (key, account) in accounts {
+        source.insert(key, account);
+    }
+
+    Arc::new(AppContext::with_account_source(
+        AppConfig::default(),
+        Arc::new(RpcClient::new_mock("succeeds".to_string())),
+        Arc::new(source),
+    ))
+}
+
+/// An initialized SPL Token mint
+pub fn mint_account(decimals: u8) -> Account {
+    let mut data = vec![0u8; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::None,
+            supply: u64::MAX,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .expect("mint fits its length");
+    account(data, spl_token::ID)
+}
+
+/// A pair account of the DLMM program with every field `edit` leaves alone at zero
+pub fn pair_account(edit: impl FnOnce(&mut Pair)) -> Account {
+    let mut data = vec![0u8; Pair::LEN];
+    data[..8].copy_from_slice(&anchor_discriminator("account:Pair"));
+    let mut pair = Pair::unpack_unchecked(&data).expect("a zeroed pair decodes");
+    edit(&mut pair);
+    Pair::pack(pair, &mut data).expect("pair fits its length");
+    account(data, DLMM_PROGRAM_ID)
+}
+
+/// The bin array with the given index, each bin holding the reserves `reserves` gives for its id
+pub fn bin_array_account(index: u32, reserves: impl Fn(u32) -> (u64, u64)) -> Account {
+    let mut data = vec![0u8; BinArray::LEN];
+    data[..8].copy_from_slice(&anchor_discriminator("account:BinArray"));
+    let mut bin_array = BinArray::unpack_unchecked(&data).expect("a zeroed bin array decodes");
+    for (offset, bin) in bin_array.bins.iter_mut().enumerate() {
+        let (reserve_x, reserve_y) = reserves(index * BIN_ARRAY_SIZE + offset as u32);
+        bin.reserve_x = reserve_x;
+        bin.reserve_y = reserve_y;
+        bin.total_supply = reserve_x as u128 + reserve_y as u128;
+    }
+    BinArray::pack(bin_array, &mut data).expect("bin array fits its length");
+    account(data, DLMM_PROGRAM_ID)
+}
+
+/// Reads accounts straight from the bank, so the app sees what the program wrote
+pub struct BanksAccountSource {
+    pub banks_client: BanksClient,
+}
+
+#[async_trait]
+impl AccountSource for BanksAccountSource {
+    async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let mut banks_client = self.banks_client.clone();
+        let slot = banks_client.get_root_slot().await?;
+        let mut accounts = Vec::with_capacity(keys.len());
+        for key in keys {
+            accounts.push(banks_client.get_account(*key).await?);
+        }
+        Ok((slot, accounts))
+    }
+
+    async fn get_program_accounts_with_slot(
+        &self,
+        program_id: &Pubkey,
+        _filters: &[DataFilter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>)> {
+        bail!("The test bank cannot list the accounts of {}", program_id)
+    }
+}
+
+/// Every account recorded in `dir` but programs and sysvars, which the bank provides itself.
+/// `None` when nothing was recorded there and `REQUIRE_PROGRAM_FIXTURES` is unset.
+pub fn read_fixtures(dir: &str) -> Result<Option<Vec<(Pubkey, Account)>>> {
+    if !fixtures_present(&[dir])? {
+        return Ok(None);
+    }
+
+    let dir = Path::new(dir);
+    let mut accounts = vec![];
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to list the fixtures in {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+
+        let fixture: AccountFixture = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("Failed to parse fixture {}", path.display()))?;
+        let (key, account) = fixture.decode()?;
+        if !account.executable && account.owner != sysvar::ID {
+            accounts.push((key, account));
+        }
+    }
+    Ok(Some(accounts))
+}
+
+/// A token account holding `amount`, wrapped SOL accounts also hold the lamports backing it
+pub fn token_account(
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    token_program: &Pubkey,
+) -> Account {
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+    let is_native = *mint == native_mint::ID;
+
+    let mut data = vec![0u8; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: if is_native {
+                COption::Some(rent)
+            } else {
+                COption::None
+            },
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        &mut data,
+    )
+    .expect("token account fits its length");
+
+    Account {
+        lamports: if is_native { rent + amount } else { rent },
+        data,
+        owner: *token_program,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub struct TestBank {
+    pub context: ProgramTestContext,
+    pub pair: PairSummary,
+    pub user: Keypair,
+}
+
+impl TestBank {
+    /// Starts the bank with the recorded pair and a user holding 10 SOL and `token_amount`
+    /// of both pair tokens in their associated token accounts. `None` when the program or the
+    /// pair was not recorded and `REQUIRE_PROGRAM_FIXTURES` is unset.
+    pub async fn start(token_amount: u64) -> Result<Option<Self>> {
+        if !fixtures_present(&[PROGRAM_FIXTURE])? {
+            return Ok(None);
+        }
+        let Some(fixtures) = read_fixtures(PAIR_FIXTURES_DIR)? else {
+            return Ok(None);
+        };
+        let source = MemoryAccountSource::new(fixtures.iter().cloned().collect());
+        let pair = fetch_program_pairs(Arc::new(source), DLMM_PROGRAM_ID)
+            .await?
+            .into_iter()
+            .next()
+            .context("The fixtures hold no pair")?;
+
+        let mut program_test = ProgramTest::new("saros_dlmm", DLMM_PROGRAM_ID, None);
+        program_test.prefer_bpf(true);
+        for (key, account) in &fixtures {
+            program_test.add_account(*key, account.clone());
+        }
+
+        let user = Keypair::new();
+        program_test.add_account(
+            user.pubkey(),
+            Account {
+                lamports: 10 * LAMPORTS_PER_SOL,
+                owner: system_program::ID,
+                ..Account::default()
+            },
+        );
+        for mint in [pair.token_mint_x, pair.token_mint_y] {
+            let token_program = fixtures
+                .iter()
+                .find(|(key, _)| *key == mint)
+                .map(|(_, account)| account.owner)
+                .with_context(|| format!("Mint {} was not recorded", mint))?;
+            program_test.add_account(
+                get_associated_token_address_with_program_id(&user.pubkey(), &mint, &token_program),
+                token_account(&mint, &user.pubkey(), token_amount, &token_program),
+            );
+        }
+
+        Ok(Some(TestBank {
+            context: program_test.start_with_context().await,
+            pair,
+            user,
+        }))
+    }
+
+    /// A fresh app reading from the bank. Its RPC client answers the bank's latest blockhash
+    /// once, which is all a transaction builder asks it for.
+    pub async fn app_context(&mut self) -> Result<AppContext> {
+        let blockhash = self.context.banks_client.get_latest_blockhash().await?;
+        let rpc_client = RpcClient::new_mock_with_mocks(
+            "succeeds".to_string(),
+            HashMap::from([(
+                RpcRequest::GetLatestBlockhash,
+                json!({
+                    "context": { "slot": 1 },
+                    "value": {
+                        "blockhash": blockhash.to_string(),
+                        "lastValidBlockHeight": u64::MAX,
+                    },
+                }),
+            )]),
+        );
+
+        Ok(AppContext::with_account_source(
+            AppConfig::default(),
+            Arc::new(rpc_client),
+            Arc::new(BanksAccountSource {
+                banks_client: self.context.banks_client.clone(),
+            }),
+        ))
+    }
+
+    /// Adds the user's signature to a base64 transaction built by the app and lands it
+    pub async fn sign_and_process(&mut self, transaction: &str) -> Result<()> {
+        let mut transaction: VersionedTransaction =
+            bincode::deserialize(&STANDARD.decode(transaction)?)?;
+        let user_index = transaction
+            .message
+            .static_account_keys()
+            .iter()
+            .position(|key| *key == self.user.pubkey())
+            .context("The user does not sign the transaction")?;
+        transaction.signatures[user_index] =
+            self.user.sign_message(&transaction.message.serialize());
+
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn token_balance(&mut self, mint: &Pubkey) -> Result<u64> {
+        let token_program = self
+            .context
+            .banks_client
+            .get_account(*mint)
+            .await?
+            .with_context(|| format!("Mint {} not found", mint))?
+            .owner;
+        let token_account =
+            get_associated_token_address_with_program_id(&self.user.pubkey(), mint, &token_program);
+
+        Ok(self
+            .context
+            .banks_client
+            .get_account(token_account)
+            .await?
+            .and_then(|account| token_account_amount(&account.data))
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{bail, Context, Result};
+
+/// First 8 bytes of `sha256(preimage)`, the tag Anchor puts in front of instruction data
+/// (`global:<instruction>`) and account data (`account:<Account>`)
+pub fn anchor_discriminator(preimage: &str) -> [u8; 8] {
+    let hash = solana_program::hash::hash(preimage.as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+/// Scales a human-readable amount (e.g. `1.5`) into raw token units
+pub fn parse_ui_amount(amount: &str, decimals: u8) -> Result<u64> {
+    let amount = amount.trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    if whole.is_empty() && fraction.is_empty() {
+        bail!("Amount is empty");
+    }
+    if !whole.chars().all(|c| c.is_ascii_digit()) || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Invalid amount: {}", amount);
+    }
+    if fraction.len() > decimals as usize {
+        bail!(
+            "Amount {} has more than {} decimal places",
+            amount,
+            decimals
+        );
+    }
+
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().context("Amount is too large")?
+    };
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = decimals as usize).parse()?
+    };
+
+    let raw = 10u128
+        .checked_pow(decimals as u32)
+        .and_then(|scale| whole.checked_mul(scale))
+        .and_then(|whole| whole.checked_add(fraction))
+        .context("Amount is too large")?;
+
+    u64::try_from(raw).context("Amount is too large")
+}
+
+/// Converts raw token units into a human-readable amount
+pub fn to_ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchor_discriminator_matches_anchor() {
+        // The tag of every Anchor `swap` instruction, Whirlpool's included
+        assert_eq!(
+            anchor_discriminator("global:swap"),
+            [248, 198, 158, 145, 225, 117, 135, 200]
+        );
+    }
+
+    #[test]
+    fn parse_ui_amount_scales_by_the_decimals() {
+        assert_eq!(parse_ui_amount("1.5", 6).unwrap(), 1_500_000);
+        assert_eq!(parse_ui_amount("2", 6).unwrap(), 2_000_000);
+        assert_eq!(parse_ui_amount(".25", 6).unwrap(), 250_000);
+        assert_eq!(parse_ui_amount("3.", 6).unwrap(), 3_000_000);
+        assert_eq!(parse_ui_amount(" 0.000001 ", 6).unwrap(), 1);
+        assert_eq!(parse_ui_amount("42", 0).unwrap(), 42);
+    }
+
+    #[test]
+    fn parse_ui_amount_rejects_more_decimals_than_the_mint() {
+        assert!(parse_ui_amount("0.0000001", 6).is_err());
+        assert!(parse_ui_amount("1.0", 0).is_err());
+    }
+
+    #[test]
+    fn parse_ui_amount_rejects_what_is_not_a_number() {
+        for amount in ["", ".", "-1", "+1", "1e3", "1.2.3", "1,5", "one"] {
+            assert!(parse_ui_amount(amount, 6).is_err(), "{:?}", amount);
+        }
+    }
+
+    #[test]
+    fn parse_ui_amount_rejects_what_overflows_a_u64() {
+        assert_eq!(
+            parse_ui_amount("18446744073709551615", 0).unwrap(),
+            u64::MAX
+        );
+        assert!(parse_ui_amount("18446744073709551616", 0).is_err());
+        assert_eq!(
+            parse_ui_amount("18446744073.709551615", 9).unwrap(),
+            u64::MAX
+        );
+        assert!(parse_ui_amount("18446744074", 9).is_err());
+        // Past what even the intermediate u128 holds
+        assert!(parse_ui_amount(&"9".repeat(40), 0).is_err());
+        assert!(parse_ui_amount("1", 40).is_err());
+    }
+}
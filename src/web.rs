@@ -1,10 +1,15 @@
 use saros_sdk::utils::helper::is_swap_for_y;
+use serde::Deserialize;
 use solana_sdk::pubkey::Pubkey;
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use tracing::info;
+use std::{collections::HashMap, net::SocketAddr, str::FromStr, sync::Arc};
+use tracing::{debug, info};
 
 use axum::{
-    extract::{Query, State},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
     routing::{get, post},
     Json, Router,
 };
@@ -16,14 +21,28 @@ use tower_http::{
 
 use crate::{
     app::{AppConfig, AppContext},
-    state::{QuoteRequest, Status, WebJsonResponse},
+    dlmm::{
+        self,
+        fees::{base_fee, fee_to_bps, variable_fee},
+        quote_pair, FeedSubscription, QuoteInput, RpcSwapSimulator,
+    },
+    error::{AppError, AppJson, AppQuery},
+    pricing::ui_price_from_id,
+    state::{
+        AddLiquidityRequest, BuildSwapRequest, ClaimRewardsRequest, CreatePositionRequest,
+        PairQuery, PositionEntry, PositionRange, QuoteRequest, QuoteSubscription, QuoteSwapMode,
+        RemoveLiquidityRequest, SimulateSwapRequest, Status, WebJsonResponse,
+    },
+    utils::to_ui_amount,
 };
 use anyhow::Result;
 
-use jupiter_amm_interface::{Amm, QuoteParams, SwapMode};
+use jupiter_amm_interface::Amm;
 
 pub async fn start_web_server(config: AppConfig) -> Result<()> {
     let app_state = Arc::new(AppContext::new(config));
+    app_state.spawn_pair_registry_refresh();
+    app_state.spawn_clock_refresh();
 
     let static_files = ServeDir::new(format!("{}/web/dist", env!("CARGO_MANIFEST_DIR")));
 
@@ -36,8 +55,19 @@ pub async fn start_web_server(config: AppConfig) -> Result<()> {
 
     let sdk_routes = Router::new()
         .route("/api/pair", get(get_pair))
+        .route("/api/pair/bins", get(get_pair_bins))
+        .route("/api/pairs", get(get_pairs))
+        .route("/api/pairs/by-mints", get(get_pairs_by_mints))
+        .route("/api/position", get(get_position))
+        .route("/api/position/create", post(create_position))
+        .route("/api/position/add-liquidity", post(add_liquidity))
+        .route("/api/position/remove-liquidity", post(remove_liquidity))
+        .route("/api/position/claim-rewards", post(claim_rewards))
+        .route("/api/positions", get(get_positions))
         .route("/api/quote", post(get_quote))
-        .route("/api/simulate", post(simulate_swap));
+        .route("/api/simulate", post(simulate_swap))
+        .route("/api/swap/build", post(build_swap))
+        .route("/api/ws", get(quote_stream));
 
     // Define API routes
     let app = Router::new()
@@ -49,7 +79,7 @@ pub async fn start_web_server(config: AppConfig) -> Result<()> {
         .with_state(app_state);
 
     let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
+    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse()?;
     let listener = tokio::net::TcpListener::bind(addr).await?;
     println!("Web server listening on http://{}", addr);
     axum::serve(listener, app.into_make_service()).await?;
@@ -62,56 +92,43 @@ async fn ping() -> &'static str {
     "pong 🦀"
 }
 
+fn invalid_address() -> AppError {
+    AppError::InvalidInput("Invalid address format".to_string())
+}
+
+fn invalid_mint() -> AppError {
+    AppError::InvalidInput("Invalid mint format".to_string())
+}
+
 /// Get pool info by pubkey
 #[axum::debug_handler]
 async fn get_pair(
     State(ctx): State<Arc<AppContext>>,
-    Query(params): Query<HashMap<String, String>>,
-) -> Json<WebJsonResponse> {
+    AppQuery(params): AppQuery<HashMap<String, String>>,
+) -> Result<Json<WebJsonResponse>, AppError> {
     let pair_address = params.get("address").cloned().unwrap_or_default();
-    if pair_address.len() < 20 {
-        return Json(WebJsonResponse {
-            status: Status::Error,
-            message: "Invalid address format".to_string(),
-            data: json!({}),
-        });
-    }
-
-    let pair_key = Pubkey::from_str_const(&pair_address);
+    let pair_key = Pubkey::from_str(&pair_address).map_err(|_| invalid_address())?;
 
     // Step 1: Get or create DLMM client
-    let dlmm_client = match ctx.get_or_spawn_client(pair_key).await {
-        Ok(client) => client,
-        Err(e) => {
-            return Json(WebJsonResponse {
-                status: Status::Error,
-                message: format!("Failed to get DLMM client: {}", e),
-                data: json!({}),
-            });
-        }
-    };
+    let dlmm_client = ctx.get_or_spawn_client(pair_key).await?;
 
     info!("🔍 Fetching metadata from RPC for pair {}", pair_address);
-    let saros_dlmm = dlmm_client.saros_dlmm.read().await;
-
-    let [mint_a_meta, mint_b_meta] = match ctx.fetch_pair_token_info(&saros_dlmm).await {
-        Ok(mints) => mints,
-        Err(e) => {
-            return Json(WebJsonResponse {
-                status: Status::Error,
-                message: format!("Failed to fetch token metadata: {}", e),
-                data: json!({}),
-            });
-        }
-    };
+    let summary = dlmm_client.pair_summary().await;
+
+    let [mint_a_meta, mint_b_meta] = ctx
+        .fetch_pair_token_info(summary.token_mint_x, summary.token_mint_y)
+        .await?;
+    let market = ctx.get_pair_market(&summary).await?;
+    let pair = &market.pair;
+    let price = ui_price_from_id(pair.bin_step, pair.active_id, &mint_a_meta, &mint_b_meta);
 
-    Json(WebJsonResponse {
+    Ok(Json(WebJsonResponse {
         status: Status::Success,
         message: "Pair fetched successfully".to_string(),
         data: json!({
             "pair_address": pair_address,
-            "token_mint_x": saros_dlmm.pair.token_mint_x.to_string(),
-            "token_mint_y": saros_dlmm.pair.token_mint_y.to_string(),
+            "token_mint_x": summary.token_mint_x.to_string(),
+            "token_mint_y": summary.token_mint_y.to_string(),
             "token_a": {
                 "mint": mint_a_meta.mint.to_string(),
                 "symbol": mint_a_meta.symbol,
@@ -122,122 +139,663 @@ async fn get_pair(
                 "symbol": mint_b_meta.symbol,
                 "decimals": mint_b_meta.decimals,
             },
+            "active_id": pair.active_id,
+            "bin_step": pair.bin_step,
+            "price_x_in_y": price,
+            "price_y_in_x": 1.0 / price,
+            "base_fee_bps": fee_to_bps(base_fee(pair)),
+            "variable_fee_bps": fee_to_bps(variable_fee(
+                pair,
+                pair.dynamic_fee_parameters.volatility_accumulator
+            )),
+            "protocol_share_bps": pair.static_fee_parameters.protocol_share,
+            "reserve_x": to_ui_amount(market.reserve_x, mint_a_meta.decimals),
+            "reserve_y": to_ui_amount(market.reserve_y, mint_b_meta.decimals),
+            "slot": market.slot,
         }),
-    })
+    }))
+}
+
+/// List the pairs found by the registry, optionally filtered by mint
+#[axum::debug_handler]
+async fn get_pairs(
+    State(ctx): State<Arc<AppContext>>,
+    AppQuery(query): AppQuery<PairQuery>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let mint = query
+        .mint
+        .as_deref()
+        .map(Pubkey::from_str)
+        .transpose()
+        .map_err(|_| invalid_mint())?;
+
+    let registry = ctx.pair_registry.read().await;
+    if registry.last_refreshed.is_none() {
+        return Err(AppError::NotReady(
+            "Pair registry is still loading".to_string(),
+        ));
+    }
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Pairs fetched successfully".to_string(),
+        data: json!(registry.query(mint, &query)),
+    }))
+}
+
+#[derive(Deserialize)]
+struct PairsByMintsQuery {
+    a: String,
+    b: String,
+}
+
+/// Find every pair trading the two mints, whichever is token X
+#[axum::debug_handler]
+async fn get_pairs_by_mints(
+    State(ctx): State<Arc<AppContext>>,
+    AppQuery(params): AppQuery<PairsByMintsQuery>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let mint_a = Pubkey::from_str(&params.a).map_err(|_| invalid_mint())?;
+    let mint_b = Pubkey::from_str(&params.b).map_err(|_| invalid_mint())?;
+
+    let pairs = ctx.find_pairs_by_mints(mint_a, mint_b).await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Pairs fetched successfully".to_string(),
+        data: json!(pairs),
+    }))
+}
+
+#[derive(Deserialize)]
+struct PairBinsQuery {
+    address: String,
+    from: Option<u32>,
+    to: Option<u32>,
+}
+
+/// List the liquidity of each bin in a range of the pair
+#[axum::debug_handler]
+async fn get_pair_bins(
+    State(ctx): State<Arc<AppContext>>,
+    AppQuery(params): AppQuery<PairBinsQuery>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let pair_key = Pubkey::from_str(&params.address).map_err(|_| invalid_address())?;
+
+    let bins = ctx
+        .fetch_bin_range(pair_key, params.from, params.to)
+        .await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Bins fetched successfully".to_string(),
+        data: json!(bins),
+    }))
+}
+
+#[derive(Deserialize)]
+struct PositionQuery {
+    address: String,
+    entry_amount_x: Option<f64>,
+    entry_amount_y: Option<f64>,
+    entry_price: Option<f64>,
+}
+
+/// Value a position, with its PnL when the entry snapshot is given
+#[axum::debug_handler]
+async fn get_position(
+    State(ctx): State<Arc<AppContext>>,
+    AppQuery(params): AppQuery<PositionQuery>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let address = Pubkey::from_str(&params.address).map_err(|_| invalid_address())?;
+    let entry = PositionEntry::from_parts(
+        params.entry_amount_x,
+        params.entry_amount_y,
+        params.entry_price,
+    )?;
+
+    let position = ctx.get_position(address, entry).await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Position fetched successfully".to_string(),
+        data: json!(position),
+    }))
+}
+
+/// Build the transaction opening a position and depositing into it, for the user to sign
+#[axum::debug_handler]
+async fn create_position(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<CreatePositionRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let (Ok(pair_key), Ok(user)) = (
+        Pubkey::from_str(&body.pair_address),
+        Pubkey::from_str(&body.user),
+    ) else {
+        return Err(invalid_address());
+    };
+    let range = PositionRange::from_parts(
+        body.lower_bin_id,
+        body.upper_bin_id,
+        body.min_price,
+        body.max_price,
+    )?;
+
+    info!(
+        "🧱 Building position on {}: {:?}, amount_x={}, amount_y={}, shape={:?}",
+        pair_key, range, body.amount_x, body.amount_y, body.shape
+    );
+
+    let position = dlmm::build_create_position_transaction(
+        &ctx,
+        pair_key,
+        user,
+        range,
+        body.amount_x,
+        body.amount_y,
+        body.shape,
+    )
+    .await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Position transaction built".to_string(),
+        data: json!(position),
+    }))
+}
+
+/// Build a deposit into an existing position, with the tokens going to each bin
+#[axum::debug_handler]
+async fn add_liquidity(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<AddLiquidityRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let (Ok(position_address), Ok(user)) = (
+        Pubkey::from_str(&body.position_address),
+        Pubkey::from_str(&body.user),
+    ) else {
+        return Err(invalid_address());
+    };
+
+    info!(
+        "🧱 Adding liquidity to {}: amount_x={}, amount_y={}, shape={:?}",
+        position_address, body.amount_x, body.amount_y, body.shape
+    );
+
+    let change = dlmm::build_add_liquidity_transaction(
+        &ctx,
+        position_address,
+        user,
+        body.amount_x,
+        body.amount_y,
+        body.shape,
+    )
+    .await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Add liquidity transaction built".to_string(),
+        data: json!(change),
+    }))
+}
+
+/// Build a withdrawal from an existing position, with the tokens returned by each bin
+#[axum::debug_handler]
+async fn remove_liquidity(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<RemoveLiquidityRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let (Ok(position_address), Ok(user)) = (
+        Pubkey::from_str(&body.position_address),
+        Pubkey::from_str(&body.user),
+    ) else {
+        return Err(invalid_address());
+    };
+
+    info!(
+        "🧱 Removing {} bps of liquidity from {}",
+        body.bps, position_address
+    );
+
+    let change = dlmm::build_remove_liquidity_transaction(
+        &ctx,
+        position_address,
+        user,
+        body.bps,
+        body.bin_ids,
+    )
+    .await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Remove liquidity transaction built".to_string(),
+        data: json!(change),
+    }))
+}
+
+/// Build the transactions claiming the rewards of every position held by a wallet
+#[axum::debug_handler]
+async fn claim_rewards(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<ClaimRewardsRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let user = Pubkey::from_str(&body.user).map_err(|_| invalid_address())?;
+
+    info!("🎁 Claiming rewards of the positions held by {}", user);
+
+    let simulator = RpcSwapSimulator {
+        rpc_client: ctx.rpc_client.clone(),
+    };
+    let claims = dlmm::build_claim_rewards_transactions(&ctx, &simulator, user).await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Claim rewards transactions built".to_string(),
+        data: json!(claims),
+    }))
+}
+
+#[derive(Deserialize)]
+struct PositionsQuery {
+    owner: String,
+}
+
+/// List the DLMM positions held by a wallet
+#[axum::debug_handler]
+async fn get_positions(
+    State(ctx): State<Arc<AppContext>>,
+    AppQuery(params): AppQuery<PositionsQuery>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let owner = Pubkey::from_str(&params.owner).map_err(|_| invalid_address())?;
+
+    let positions = ctx.get_positions(owner).await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Positions fetched successfully".to_string(),
+        data: json!(positions),
+    }))
 }
 
 #[axum::debug_handler]
 async fn get_quote(
     State(ctx): State<Arc<AppContext>>,
-    Json(body): Json<QuoteRequest>,
-) -> Json<WebJsonResponse> {
+    AppJson(body): AppJson<QuoteRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
     let pair_address = body.pair_address.clone();
 
     info!("🔍 Getting quote for pair {}", pair_address);
-    info!("Body: {:?}", body);
+    debug!("Quote request: {:?}", body);
 
-    let source_mint = Pubkey::from_str_const(&body.source_mint);
-    let destination_mint = Pubkey::from_str_const(&body.destination_mint);
+    let pair_key = Pubkey::from_str(&pair_address).map_err(|_| invalid_address())?;
+    let source_mint = Pubkey::from_str(&body.source_mint).map_err(|_| invalid_mint())?;
+    let destination_mint = Pubkey::from_str(&body.destination_mint).map_err(|_| invalid_mint())?;
 
     // 1️⃣ take DLMM client
-    let dlmm_client = match ctx
-        .get_or_spawn_client(Pubkey::from_str_const(&pair_address))
-        .await
-    {
-        Ok(client) => client,
-        Err(e) => {
-            return Json(WebJsonResponse {
-                status: Status::Error,
-                message: format!("Failed to get DLMM client: {}", e),
-                data: json!({}),
-            });
-        }
-    };
+    let dlmm_client = dlmm::live_client(&ctx, pair_key).await?;
 
     tracing::info!(
-        "💱 Quoting swap: amount_in={}, source_mint={}",
-        body.amount_in,
+        "💱 Quoting swap: amount={}, swap_mode={:?}, source_mint={}",
+        body.amount,
+        body.swap_mode,
         body.source_mint
     );
 
-    for _ in 0..3 {
-        if let Err(e) = dlmm_client.update(&ctx).await {
-            tracing::warn!("⚠️ Failed to update DLMM client: {}", e);
-            continue;
+    let client = dlmm_client.saros_dlmm.read().await;
+
+    let input = QuoteInput {
+        source_mint,
+        destination_mint,
+        amount: body.amount,
+        swap_mode: body.swap_mode,
+        slippage_bps: body.slippage_bps,
+        verbose: body.verbose,
+    };
+
+    // 2️⃣ call get_quote() from DLMM client
+    let quote = quote_pair(&ctx, &client, &input).await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "quote successful".to_string(),
+        data: json!(quote),
+    }))
+}
+
+/// Live quotes: the client sends a `QuoteSubscription` and gets a fresh quote pushed
+/// every time the pair's accounts change. Sending a new subscription replaces the old one.
+async fn quote_stream(ws: WebSocketUpgrade, State(ctx): State<Arc<AppContext>>) -> Response {
+    ws.on_upgrade(move |socket| handle_quote_stream(socket, ctx))
+}
+
+struct LiveQuote {
+    feed: FeedSubscription,
+    source_mint: Pubkey,
+    amount: u64,
+    swap_mode: QuoteSwapMode,
+    slippage_bps: u16,
+}
+
+async fn handle_quote_stream(mut socket: WebSocket, ctx: Arc<AppContext>) {
+    let mut live_quote: Option<LiveQuote> = None;
+
+    loop {
+        let response = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    match subscribe_quote(&ctx, text.as_str()).await {
+                        Ok(subscription) => {
+                            let response = live_quote_response(&ctx, &subscription).await;
+                            live_quote = Some(subscription);
+                            response
+                        }
+                        Err(e) => e.to_response(),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            changed = next_quote_update(&mut live_quote) => match changed {
+                Ok(()) => match &live_quote {
+                    Some(subscription) => live_quote_response(&ctx, subscription).await,
+                    None => continue,
+                },
+                Err(_) => {
+                    live_quote = None;
+                    AppError::Internal("Quote feed closed".to_string()).to_response()
+                }
+            },
+        };
+
+        let payload = json!(response).to_string();
+        if socket.send(Message::Text(payload.into())).await.is_err() {
+            break;
         }
     }
+}
 
-    let client = dlmm_client.saros_dlmm.read().await;
+async fn subscribe_quote(ctx: &AppContext, text: &str) -> Result<LiveQuote, AppError> {
+    let request: QuoteSubscription = serde_json::from_str(text)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid subscription: {}", e)))?;
+    let pair_key = Pubkey::from_str(&request.pair).map_err(|_| invalid_address())?;
+    let source_mint = Pubkey::from_str(&request.source_mint).map_err(|_| invalid_mint())?;
 
-    let is_swap_for_y = is_swap_for_y(source_mint, client.pair.token_mint_x);
+    let feed = dlmm::subscribe_pair(ctx, pair_key).await?;
 
-    let swap_mode = if is_swap_for_y {
-        if source_mint == client.pair.token_mint_x {
-            SwapMode::ExactIn
-        } else {
-            SwapMode::ExactOut
-        }
+    // Checked once here, otherwise every update of the feed would answer with the same error
+    let (token_mint_x, token_mint_y) = {
+        let client = feed.client.saros_dlmm.read().await;
+        (client.pair.token_mint_x, client.pair.token_mint_y)
+    };
+    if source_mint != token_mint_x && source_mint != token_mint_y {
+        return Err(AppError::InvalidInput(format!(
+            "Mint {} is not traded by pair {}",
+            source_mint, pair_key
+        )));
+    }
+
+    Ok(LiveQuote {
+        feed,
+        source_mint,
+        amount: request.amount,
+        swap_mode: request.swap_mode,
+        slippage_bps: request.slippage_bps,
+    })
+}
+
+async fn next_quote_update(
+    live_quote: &mut Option<LiveQuote>,
+) -> Result<(), tokio::sync::watch::error::RecvError> {
+    match live_quote {
+        Some(subscription) => subscription.feed.updates.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn live_quote_response(ctx: &AppContext, subscription: &LiveQuote) -> WebJsonResponse {
+    let client = subscription.feed.client.saros_dlmm.read().await;
+
+    let destination_mint = if is_swap_for_y(subscription.source_mint, client.pair.token_mint_x) {
+        client.pair.token_mint_y
     } else {
-        if source_mint == client.pair.token_mint_x {
-            SwapMode::ExactOut
-        } else {
-            SwapMode::ExactIn
-        }
+        client.pair.token_mint_x
     };
 
-    let req = QuoteParams {
-        amount: body.amount_in,
-        input_mint: source_mint,
-        swap_mode,
-        output_mint: destination_mint,
+    let input = QuoteInput {
+        source_mint: subscription.source_mint,
+        destination_mint,
+        amount: subscription.amount,
+        swap_mode: subscription.swap_mode,
+        slippage_bps: subscription.slippage_bps,
+        verbose: false,
     };
 
-    // 2️⃣ call get_quote() from DLMM client
-    let result = match client.quote(&req) {
-        Ok(quote) => Json(WebJsonResponse {
+    match quote_pair(ctx, &client, &input).await {
+        Ok(quote) => WebJsonResponse {
             status: Status::Success,
             message: "quote successful".to_string(),
             data: json!({
-                "in_amount": quote.in_amount,
-                "out_amount": quote.out_amount,
-                "fee_amount": quote.fee_amount,
-                "fee_mint": quote.fee_mint.to_string(),
+                "pair_address": client.key().to_string(),
+                "quote": quote,
             }),
-        }),
-        Err(e) => Json(WebJsonResponse {
-            status: Status::Error,
-            message: format!("Failed to get quote: {}", e),
-            data: json!({}),
-        }),
+        },
+        Err(e) => AppError::from(e).to_response(),
+    }
+}
+
+/// Build the real swap for a wallet and run it through `simulateTransaction`
+#[axum::debug_handler]
+async fn simulate_swap(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<SimulateSwapRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let keys = (
+        Pubkey::from_str(&body.pair_address),
+        Pubkey::from_str(&body.user),
+        Pubkey::from_str(&body.source_mint),
+    );
+    let (Ok(pair_key), Ok(user), Ok(source_mint)) = keys else {
+        return Err(invalid_address());
+    };
+
+    let simulator = RpcSwapSimulator {
+        rpc_client: ctx.rpc_client.clone(),
     };
 
-    result
+    let simulation = dlmm::simulate_swap(
+        &ctx,
+        &simulator,
+        pair_key,
+        user,
+        source_mint,
+        body.amount_in,
+    )
+    .await?;
+
+    Ok(Json(WebJsonResponse {
+        status: Status::Success,
+        message: "Simulation completed".to_string(),
+        data: json!(simulation),
+    }))
 }
 
-async fn simulate_swap(
-    State(_ctx): State<Arc<AppContext>>,
-    Json(body): Json<serde_json::Value>,
-) -> Json<WebJsonResponse> {
-    let pool_id = body
-        .get("pool_id")
-        .and_then(|v| v.as_str())
-        .unwrap_or("SOL-USDC");
-    let amount = body
-        .get("amount_in")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(1u64);
-
-    // mock result
-    let result = json!({
-        "pool_id": pool_id,
-        "amount_in": amount,
-        "simulated_output": amount * 98 / 100,
-        // "simulation": simulation
-    });
-
-    Json(WebJsonResponse {
+/// Build an unsigned swap transaction, ready to be handed to a wallet adapter
+#[axum::debug_handler]
+async fn build_swap(
+    State(ctx): State<Arc<AppContext>>,
+    AppJson(body): AppJson<BuildSwapRequest>,
+) -> Result<Json<WebJsonResponse>, AppError> {
+    let (Ok(pair_key), Ok(user)) = (
+        Pubkey::from_str(&body.pair_address),
+        Pubkey::from_str(&body.user),
+    ) else {
+        return Err(invalid_address());
+    };
+
+    let swap = dlmm::build_swap_transaction(
+        &ctx,
+        pair_key,
+        user,
+        body.direction,
+        body.amount_in,
+        body.slippage_bps,
+    )
+    .await?;
+
+    Ok(Json(WebJsonResponse {
         status: Status::Success,
-        message: result.to_string(),
-        data: result,
-    })
+        message: "Swap transaction built".to_string(),
+        data: json!(swap),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::{FromRequest, FromRequestParts},
+        http::{header, Request},
+    };
+
+    use super::*;
+    use crate::{
+        app::DLMM_PROGRAM_ID,
+        test_support::{account, memory_context},
+    };
+
+    fn error_code(result: Result<Json<WebJsonResponse>, AppError>) -> &'static str {
+        match result {
+            Ok(response) => panic!("the request was served: {:?}", response.0),
+            Err(error) => error.code(),
+        }
+    }
+
+    fn position_query(address: &str) -> PositionQuery {
+        PositionQuery {
+            address: address.to_string(),
+            entry_amount_x: None,
+            entry_amount_y: None,
+            entry_price: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn garbage_addresses_are_invalid_input() {
+        let ctx = memory_context([]);
+        let too_long = "1".repeat(47);
+
+        for address in ["", "not-a-pubkey", "0xdeadbeef", too_long.as_str()] {
+            let params = HashMap::from([("address".to_string(), address.to_string())]);
+            assert_eq!(
+                error_code(get_pair(State(ctx.clone()), AppQuery(params)).await),
+                "invalid_input"
+            );
+
+            let bins_query = PairBinsQuery {
+                address: address.to_string(),
+                from: None,
+                to: None,
+            };
+            assert_eq!(
+                error_code(get_pair_bins(State(ctx.clone()), AppQuery(bins_query)).await),
+                "invalid_input"
+            );
+
+            assert_eq!(
+                error_code(
+                    get_position(State(ctx.clone()), AppQuery(position_query(address))).await
+                ),
+                "invalid_input"
+            );
+
+            let quote: QuoteRequest = serde_json::from_value(json!({
+                "pair_address": address,
+                "source_mint": address,
+                "destination_mint": address,
+                "amount": 1,
+            }))
+            .unwrap();
+            assert_eq!(
+                error_code(get_quote(State(ctx.clone()), AppJson(quote)).await),
+                "invalid_input"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_pair_is_not_found() {
+        let ctx = memory_context([]);
+        let params = HashMap::from([("address".to_string(), Pubkey::new_unique().to_string())]);
+
+        assert_eq!(
+            error_code(get_pair(State(ctx), AppQuery(params)).await),
+            "pair_not_found"
+        );
+    }
+
+    #[tokio::test]
+    async fn claiming_without_rewards_is_invalid_input() {
+        let ctx = memory_context([]);
+        let body = ClaimRewardsRequest {
+            user: Pubkey::new_unique().to_string(),
+        };
+
+        assert_eq!(
+            error_code(claim_rewards(State(ctx), AppJson(body)).await),
+            "invalid_input"
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_accounts_are_decode_failure() {
+        let pair_key = Pubkey::new_unique();
+        let position_key = Pubkey::new_unique();
+        let ctx = memory_context([
+            (pair_key, account(vec![0u8; 16], DLMM_PROGRAM_ID)),
+            (position_key, account(vec![0u8; 16], DLMM_PROGRAM_ID)),
+        ]);
+
+        let params = HashMap::from([("address".to_string(), pair_key.to_string())]);
+        assert_eq!(
+            error_code(get_pair(State(ctx.clone()), AppQuery(params)).await),
+            "decode_failure"
+        );
+
+        let query = position_query(&position_key.to_string());
+        assert_eq!(
+            error_code(get_position(State(ctx), AppQuery(query)).await),
+            "decode_failure"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_query_parameter_is_invalid_input() {
+        let (mut parts, _) = Request::builder()
+            .uri("/api/pair/bins?from=10")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let Err(error) = AppQuery::<PairBinsQuery>::from_request_parts(&mut parts, &()).await
+        else {
+            panic!("a query without `address` was accepted");
+        };
+        assert_eq!(error.code(), "invalid_input");
+    }
+
+    #[tokio::test]
+    async fn malformed_json_body_is_invalid_input() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/quote")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"pair_address": "#))
+            .unwrap();
+
+        let Err(error) = AppJson::<QuoteRequest>::from_request(request, &()).await else {
+            panic!("a truncated body was accepted");
+        };
+        assert_eq!(error.code(), "invalid_input");
+    }
 }
@@ -38,7 +38,7 @@ export default function App() {
       const res = await fetch(`/api/pair?address=${address}`);
       const response = await res.json();
       console.log("Fetched pair data:", response);
-      if (response.status === "error") throw new Error(response.message);
+      if (response.status !== "ok") throw new Error(response.message);
       const data = response.data;
       setTokens({
         a: {
@@ -86,11 +86,13 @@ export default function App() {
           pair_address: pair,
           source_mint: base.address,
           destination_mint: quoteToken.address,
-          amount_in: value * 10 ** base.decimals,
+          amount: Math.round(value * 10 ** base.decimals),
+          swap_mode: "exact_in",
+          slippage_bps: 50,
         }),
       });
       const response = await res.json();
-      if (response.status === "error") {
+      if (response.status !== "ok") {
         setQuote({ error: response.message });
       } else {
         const data = response.data;
@@ -100,6 +102,10 @@ export default function App() {
           in_amount: data.in_amount,
           out_amount: data.out_amount,
           fee_amount: data.fee_amount,
+          min_out_amount: data.min_out_amount / 10 ** quoteToken.decimals,
+          slippage_bps: data.slippage_bps,
+          execution_price: data.execution_price,
+          price_impact_bps: data.price_impact_bps,
         };
 
         setAmountOut(String(data.out_amount / 10 ** quoteToken.decimals));
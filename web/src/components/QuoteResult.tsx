@@ -67,6 +67,31 @@ export default function QuoteResult({ quote, loading }: Props) {
               <span className="text-gray-500">Fee:</span>{" "}
               <span className="text-yellow-300">{quote.fee_amount}</span>
             </div>
+            <div>
+              <span className="text-gray-500">Min received:</span>{" "}
+              <span className="text-green-300">
+                {quote.output} — {quote.min_out_amount} ({quote.slippage_bps}{" "}
+                bps)
+              </span>
+            </div>
+            <div>
+              <span className="text-gray-500">Price:</span>{" "}
+              <span className="text-cyan-300">
+                {quote.execution_price} {quote.output}/{quote.input}
+              </span>
+            </div>
+            <div>
+              <span className="text-gray-500">Price impact:</span>{" "}
+              <span
+                className={
+                  quote.price_impact_bps > 100
+                    ? "text-red-400"
+                    : "text-yellow-300"
+                }
+              >
+                {(quote.price_impact_bps / 100).toFixed(2)}%
+              </span>
+            </div>
           </motion.div>
         )}
       </AnimatePresence>